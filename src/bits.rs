@@ -0,0 +1,78 @@
+// A dense matrix of bits, one row per grammar and one column per sentence in
+// a domain, so that whether a grammar generates a sentence is a single bit
+// test.
+#[derive(Clone)]
+pub struct BitMatrix {
+    words_per_row: usize,
+    words: Vec<u64>
+}
+
+impl BitMatrix {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        let words_per_row = (cols + 63) / 64;
+        BitMatrix { words_per_row, words: vec![0; rows * words_per_row] }
+    }
+
+    #[inline]
+    pub fn get(&self, row: usize, col: usize) -> bool {
+        self.words[row * self.words_per_row + col / 64] & (1 << (col % 64)) != 0
+    }
+
+    pub fn set(&mut self, row: usize, col: usize) {
+        self.words[row * self.words_per_row + col / 64] |= 1 << (col % 64);
+    }
+}
+
+// Maps sparse ids onto 0..n, for indexing a `BitMatrix`. Lookups are by
+// position rather than hashing, so ids should be reasonably small.
+#[derive(Clone)]
+pub struct DenseIndex {
+    index: Vec<u32>
+}
+
+const ABSENT: u32 = ::std::u32::MAX;
+
+impl DenseIndex {
+    /// Numbers `ids` in the order given.
+    pub fn new(ids: &[u32]) -> Self {
+        let size = ids.iter().max().map_or(0, |&m| m as usize + 1);
+        let mut index = vec![ABSENT; size];
+        for (n, id) in ids.iter().enumerate() {
+            index[*id as usize] = n as u32;
+        }
+        DenseIndex { index }
+    }
+
+    #[inline]
+    pub fn get(&self, id: u32) -> Option<usize> {
+        match self.index.get(id as usize) {
+            Some(&n) if n != ABSENT => Some(n as usize),
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bits::{BitMatrix, DenseIndex};
+
+    #[test]
+    fn bits_are_set_per_row_across_words() {
+        let mut matrix = BitMatrix::new(3, 130);
+        matrix.set(1, 0);
+        matrix.set(1, 64);
+        matrix.set(2, 129);
+        let set: Vec<(usize, usize)> = (0..3)
+            .flat_map(|r| (0..130).map(move |c| (r, c)))
+            .filter(|&(r, c)| matrix.get(r, c))
+            .collect();
+        assert_eq!(set, vec![(1, 0), (1, 64), (2, 129)]);
+    }
+
+    #[test]
+    fn ids_are_numbered_in_order() {
+        let index = DenseIndex::new(&[7, 2, 40]);
+        assert_eq!((index.get(7), index.get(2), index.get(40)), (Some(0), Some(1), Some(2)));
+        assert_eq!((index.get(3), index.get(41), index.get(u32::max_value())), (None, None, None));
+    }
+}
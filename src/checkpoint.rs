@@ -0,0 +1,358 @@
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Write, BufWriter};
+use std::str::FromStr;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+
+use rand;
+use rand::{Rng, SeedableRng};
+use mersenne_twister::MersenneTwister;
+
+// Saving the state of learners and speakers part way through a run, so that a
+// killed run can carry on exactly where it left off. Everything that draws
+// random numbers does so from a `ResumableRng`, and everything built for a run
+// is built after `seed_thread`, so that a resumed run rebuilds the same
+// learners and speakers and then restores the state they had.
+
+// Learners and speakers whose state can be saved and restored.
+pub trait Checkpoint {
+    /// The state that changes as it runs, on one line.
+    fn save(&self) -> String;
+    /// Restores state written by `save` to one built the same way, from the
+    /// same seed.
+    fn restore(&mut self, state: &str) -> Result<(), String>;
+}
+
+impl<T: Checkpoint + ?Sized> Checkpoint for Box<T> {
+    fn save(&self) -> String {
+        (**self).save()
+    }
+    fn restore(&mut self, state: &str) -> Result<(), String> {
+        (**self).restore(state)
+    }
+}
+
+/// Joins the saved fields of one object. Only the last field may contain
+/// `; ` itself, so nested state goes last.
+pub fn join(fields: &[String]) -> String {
+    fields.join("; ")
+}
+
+/// Splits state written by `join` into its `n` fields.
+pub fn split(state: &str, n: usize) -> Result<Vec<&str>, String> {
+    let fields: Vec<&str> = state.splitn(n, "; ").collect();
+    if fields.len() != n {
+        return Err(format!("Expected {} fields, saw {}: {}", n, fields.len(), state));
+    }
+    Ok(fields)
+}
+
+/// Writes space-separated values.
+pub fn save_list<T: ToString>(values: &[T]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(" ")
+}
+
+/// Reads values written by `save_list`.
+pub fn restore_list<T: FromStr>(s: &str) -> Result<Vec<T>, String> {
+    s.split_whitespace()
+        .map(|v| v.parse::<T>().map_err(|_| format!("Bad value {} in {}", v, s)))
+        .collect()
+}
+
+// The number of consecutive outputs that pin down a Mersenne Twister.
+const MT_OUTPUTS: usize = 312;
+
+// A Mersenne Twister whose state can be saved. The generator's state is
+// private, so saving draws the next outputs from a copy of it: restoring
+// replays those outputs and then carries on with a generator recovered from
+// them.
+#[derive(Clone)]
+pub struct ResumableRng {
+    replay: VecDeque<u64>,
+    rng: MersenneTwister
+}
+
+impl ResumableRng {
+    /// The outputs the generator will give next, enough to recover it.
+    pub fn save(&self) -> String {
+        let mut rng = self.rng.clone();
+        let outputs: Vec<u64> = self.replay.iter()
+            .cloned()
+            .chain((0..MT_OUTPUTS).map(|_| rng.next_u64()))
+            .collect();
+        save_list(&outputs)
+    }
+
+    pub fn restore(state: &str) -> Result<Self, String> {
+        let outputs: Vec<u64> = restore_list(state)?;
+        if outputs.len() < MT_OUTPUTS {
+            return Err(format!("Expected at least {} generator outputs, saw {}",
+                               MT_OUTPUTS, outputs.len()));
+        }
+        let rng = MersenneTwister::recover(&outputs[outputs.len() - MT_OUTPUTS..]);
+        Ok(ResumableRng { replay: outputs.into_iter().collect(), rng })
+    }
+}
+
+impl SeedableRng<u64> for ResumableRng {
+    fn reseed(&mut self, seed: u64) {
+        self.replay.clear();
+        self.rng.reseed(seed);
+    }
+
+    fn from_seed(seed: u64) -> Self {
+        ResumableRng { replay: VecDeque::new(), rng: MersenneTwister::from_seed(seed) }
+    }
+}
+
+impl Rng for ResumableRng {
+    // the 64 bit Mersenne Twister's next_u32 is the bottom of next_u64
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self.replay.pop_front() {
+            Some(output) => output,
+            None => self.rng.next_u64()
+        }
+    }
+}
+
+thread_local!(static SEEDS: RefCell<Option<MersenneTwister>> = RefCell::new(None));
+
+/// Makes the generators built on this thread from now on follow `seed`, so
+/// that what is built from them can be built again.
+pub fn seed_thread(seed: u64) {
+    SEEDS.with(|seeds| *seeds.borrow_mut() = Some(MersenneTwister::from_seed(seed)));
+}
+
+/// A new generator, seeded from the thread's seed if `seed_thread` has been
+/// called and at random otherwise.
+pub fn new_rng() -> ResumableRng {
+    let seed = SEEDS.with(|seeds| match *seeds.borrow_mut() {
+        Some(ref mut seeds) => seeds.next_u64(),
+        None => rand::thread_rng().next_u64()
+    });
+    ResumableRng::from_seed(seed)
+}
+
+// A log is compacted once it's this many times the size it was last compacted
+// to, or to `COMPACT_MIN` bytes if that's more.
+const COMPACT_RATIO: u64 = 4;
+const COMPACT_MIN: u64 = 1 << 20;
+
+// The progress of an experiment made of numbered trials, kept in a file if it
+// has one. Each trial is seeded from the experiment's seed and its number, so
+// a trial can be rebuilt and restored on its own, on any thread.
+//
+// The file is a log: a `seed SEED` line, then `partial TRIAL STATE` each time
+// a trial saves how far it got and `done TRIAL RESULT` when it finishes, with
+// the result and state written however the experiment likes. Later lines win.
+// Saving appends a line, and opening compacts the log down to the latest line
+// for each trial. Trials can save large states often, so the log is compacted
+// again whenever it grows to `COMPACT_RATIO` times what it was compacted to.
+pub struct Experiment {
+    log: Option<File>,
+    path: String,
+    // the bytes in the log, and in it when last compacted
+    size: u64,
+    compacted: u64,
+    pub seed: u64,
+    done: HashMap<usize, String>,
+    partial: HashMap<usize, String>
+}
+
+impl Experiment {
+    /// A new experiment that isn't saved anywhere.
+    pub fn new() -> Self {
+        Experiment { log: None, path: String::new(), size: 0, compacted: 0,
+                     seed: rand::thread_rng().next_u64(),
+                     done: HashMap::new(), partial: HashMap::new() }
+    }
+
+    /// Picks up the experiment checkpointed at `path`, or starts a new one
+    /// there if there's no file yet.
+    pub fn open(path: &str) -> io::Result<Self> {
+        let mut experiment = match fs::read_to_string(path) {
+            Ok(contents) => Experiment::read(path, &contents)?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Experiment::new(),
+            Err(e) => return Err(e)
+        };
+        experiment.path = path.to_string();
+        experiment.compact()?;
+        Ok(experiment)
+    }
+
+    // A run killed while appending can leave the last line cut short, so a
+    // last line without its newline is dropped.
+    fn read(path: &str, contents: &str) -> io::Result<Self> {
+        let mut lines: Vec<&str> = contents.split('\n').collect();
+        lines.pop();
+        let mut experiment = Experiment::new();
+        let mut seed = None;
+        for line in lines {
+            let fields: Vec<&str> = line.splitn(3, ' ').collect();
+            match (fields[0], fields.get(1).map(|t| t.parse::<u64>())) {
+                ("seed", Some(Ok(s))) => seed = Some(s),
+                ("done", Some(Ok(trial))) if fields.len() == 3 => {
+                    experiment.partial.remove(&(trial as usize));
+                    experiment.done.insert(trial as usize, fields[2].to_string());
+                },
+                ("partial", Some(Ok(trial))) if fields.len() == 3 =>
+                    { experiment.partial.insert(trial as usize, fields[2].to_string()); },
+                _ => return Err(invalid(format!("{}: bad line: {}", path, line)))
+            }
+        }
+        experiment.seed = seed.ok_or_else(|| invalid(format!("{}: no seed", path)))?;
+        Ok(experiment)
+    }
+
+    /// Seeds this thread for `trial` (see `seed_thread`).
+    pub fn seed_trial(&self, trial: usize) {
+        seed_thread(self.seed.wrapping_add(trial as u64));
+    }
+
+    /// What `trial` finished with, if it has.
+    pub fn finished(&self, trial: usize) -> Option<&String> {
+        self.done.get(&trial)
+    }
+
+    /// How far `trial` got, if it started but didn't finish.
+    pub fn progress(&self, trial: usize) -> Option<&String> {
+        self.partial.get(&trial)
+    }
+
+    /// Records that `trial` got as far as `state`.
+    pub fn save(&mut self, trial: usize, state: String) -> io::Result<()> {
+        self.append(&format!("partial {} {}\n", trial, state))?;
+        self.partial.insert(trial, state);
+        self.compact_if_grown()
+    }
+
+    /// Records that `trial` finished with `result`.
+    pub fn finish(&mut self, trial: usize, result: String) -> io::Result<()> {
+        self.append(&format!("done {} {}\n", trial, result))?;
+        self.partial.remove(&trial);
+        self.done.insert(trial, result);
+        self.compact_if_grown()
+    }
+
+    // One write per line, so that lines from different trials don't mix.
+    fn append(&mut self, line: &str) -> io::Result<()> {
+        match self.log {
+            Some(ref mut log) => log.write_all(line.as_bytes())?,
+            None => return Ok(())
+        }
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn compact_if_grown(&mut self) -> io::Result<()> {
+        if self.log.is_some() && self.size > COMPACT_RATIO * self.compacted.max(COMPACT_MIN) {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    // Rewrites the log with just the latest line for each trial. It's written
+    // alongside and renamed into place, so a run killed while writing leaves
+    // the old log whole. Appending carries on at the end of the new log.
+    fn compact(&mut self) -> io::Result<()> {
+        let temp = format!("{}.partial", self.path);
+        {
+            let mut w = BufWriter::new(File::create(&temp)?);
+            writeln!(w, "seed {}", self.seed)?;
+            let mut done: Vec<(&usize, &String)> = self.done.iter().collect();
+            done.sort();
+            for (trial, result) in done {
+                writeln!(w, "done {} {}", trial, result)?;
+            }
+            let mut partial: Vec<(&usize, &String)> = self.partial.iter().collect();
+            partial.sort();
+            for (trial, state) in partial {
+                writeln!(w, "partial {} {}", trial, state)?;
+            }
+            w.flush()?;
+        }
+        fs::rename(&temp, &self.path)?;
+        let log = OpenOptions::new().append(true).open(&self.path)?;
+        self.size = log.metadata()?.len();
+        self.compacted = self.size;
+        self.log = Some(log);
+        Ok(())
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use checkpoint::Experiment;
+
+    #[test]
+    fn an_experiment_reads_back_what_it_saved() {
+        let path = env::temp_dir().join(format!("experiment-{}.txt", ::std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+        let seed = {
+            let mut experiment = Experiment::open(path).unwrap();
+            experiment.save(0, "first | 1 2".to_string()).unwrap();
+            experiment.save(0, "second | 3 4".to_string()).unwrap();
+            experiment.save(1, "halfway".to_string()).unwrap();
+            experiment.finish(1, "1; 5; 0.5 0.5".to_string()).unwrap();
+            experiment.save(2, "started".to_string()).unwrap();
+            experiment.seed
+        };
+        // killed part way through appending a line
+        OpenOptions::new().append(true).open(path).unwrap()
+            .write_all(b"partial 2 cut sh").unwrap();
+
+        let experiment = Experiment::open(path).unwrap();
+        assert_eq!(experiment.seed, seed);
+        assert_eq!(experiment.progress(0).unwrap(), "second | 3 4");
+        assert_eq!(experiment.finished(0), None);
+        assert_eq!(experiment.progress(1), None);
+        assert_eq!(experiment.finished(1).unwrap(), "1; 5; 0.5 0.5");
+        assert_eq!(experiment.progress(2).unwrap(), "started");
+        assert_eq!(experiment.progress(3), None);
+        // opening compacted the log to one line per trial
+        assert_eq!(fs::read_to_string(path).unwrap(),
+                   format!("seed {}\ndone 1 1; 5; 0.5 0.5\npartial 0 second | 3 4\n\
+                            partial 2 started\n", seed));
+
+        fs::write(path, "seed 1\npartial x y\n").unwrap();
+        assert!(Experiment::open(path).is_err());
+        fs::write(path, "done 0 1; 2; 3\n").unwrap();
+        assert!(Experiment::open(path).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn an_experiment_log_stays_near_the_size_of_its_state() {
+        let path = env::temp_dir().join(format!("experiment-big-{}.txt", ::std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+        let state = "x".repeat(10_000);
+        let mut experiment = Experiment::open(path).unwrap();
+        for n in 0..2000 {
+            experiment.save(n % 3, format!("{} {}", n, state)).unwrap();
+            // the log never grows past the compaction threshold, 4 MB here
+            assert!(fs::metadata(path).unwrap().len() <= 4 << 20);
+        }
+        experiment.finish(0, "done".to_string()).unwrap();
+        let read = Experiment::open(path).unwrap();
+        assert_eq!(read.finished(0).unwrap(), "done");
+        assert!(*read.progress(1).unwrap() == format!("1999 {}", state));
+        assert!(*read.progress(2).unwrap() == format!("1997 {}", state));
+        fs::remove_file(path).unwrap();
+    }
+}
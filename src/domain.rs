@@ -1,35 +1,134 @@
 extern crate csv;
 extern crate rand;
-use std::mem;
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
 use rand::{Rng};
 use rand::distributions::{Range, Sample};
 
+use std::fs::File;
+use std::io;
+use std::io::{Read, BufRead, BufReader};
+use std::path::Path;
+use std::fmt;
 use std::error::Error;
+use std::convert::TryFrom;
+use std::sync::Arc;
 use std::collections::{HashSet, HashMap};
 
-use sentence::{SurfaceForm, Illoc};
+use sentence::{SurfaceForm, Illoc, UnknownToken};
+use tree::{Tree, TreeId};
+use bits::{BitMatrix, DenseIndex};
+use snapshot;
+use grammar;
+use triggers::{TriggerMap, NORMAL, NO_EQUIV, NO_SUPERSET};
 
-pub const NUM_PARAMS: usize = 13;
-pub type Grammar = u16;
+/// The number of parameters in the COLAG domain.
+pub const COLAG_PARAMS: usize = 13;
+pub use grammar::Grammar;
 pub type Sentence = u32;
-pub type TriggerVec = [Trigger; NUM_PARAMS];
+pub type TriggerVec = Vec<Trigger>;
+
+const COLAG_IDS: &'static str = "./data/COLAG_2011_ids.txt";
+// the trigger tables by name, all but the first optional
+const COLAG_TRIGGERS: [(&'static str, &'static str); 3] = [
+    (NORMAL, "./data/irrelevance-output.txt"),
+    (NO_EQUIV, "./data/irrelevance-output-no-equiv.txt"),
+    (NO_SUPERSET, "./data/irrelevance-output-no-superset.txt")
+];
+const COLAG_SENTENCES: &'static str = "./data/COLAG_2011_sents.txt";
+const COLAG_TREES: &'static str = "./data/COLAG_2011_trees.txt";
+const COLAG_SNAPSHOT: &'static str = "./data/COLAG_2011.snapshot";
 
 
 pub trait LanguageDomain {
-    fn language(&self, g: &Grammar) -> Result<&HashSet<Sentence>, IllegalGrammar>;
-    fn language_vec(&self, g: &Grammar) -> Result<&Vec<Sentence>, IllegalGrammar>;
-    fn surface_form(&self, g: &Sentence) -> &SurfaceForm;
-    fn triggers(&self, &Sentence) -> &TriggerVec;
-    fn parses(&self, &Grammar, &Sentence) -> Result<bool, IllegalGrammar>;
+    fn language(&self, g: &Grammar) -> Result<&HashSet<Sentence>, DomainError>;
+    fn language_vec(&self, g: &Grammar) -> Result<&Vec<Sentence>, DomainError>;
+    fn surface_form(&self, g: &Sentence) -> Result<&SurfaceForm, DomainError>;
+    fn triggers(&self, s: &Sentence) -> Result<&TriggerVec, DomainError>;
+    fn parses(&self, g: &Grammar, s: &Sentence) -> Result<bool, DomainError>;
     fn random_grammar<T: Rng>(&self, rng: &mut T) -> &Grammar;
+    fn grammars(&self) -> &Vec<Grammar>;
+    fn num_params(&self) -> usize;
+
+    /// True if `g` is one of the domain's grammars.
+    fn is_legal(&self, g: &Grammar) -> bool {
+        self.language(g).is_ok()
+    }
+
+    /// The legal grammars one parameter flip away from `g`.
+    fn legal_neighbours(&self, g: &Grammar) -> Vec<Grammar> {
+        g.neighbours(self.num_params()).into_iter()
+            .filter(|n| self.is_legal(n))
+            .collect()
+    }
 }
 
 #[derive(Debug)]
-pub struct IllegalGrammar {
-    grammar: Grammar
+pub enum DomainError {
+    // a data file that couldn't be opened or read
+    Read { filename: String, message: String },
+    // a line of a data file that isn't laid out the way we expect
+    Parse { filename: String, line: u64, message: String },
+    // a trigger, word or illocution we don't know in a data file
+    BadToken { filename: String, line: u64, token: String },
+    // data that reads fine but isn't what we expected
+    Unexpected(String),
+    IllegalGrammar(Grammar),
+    NoTriggers(Sentence),
+    NoTriggerTable(String),
+    NoSurfaceForm(Sentence),
+    NoSentence(String),
+    NoGenerators(Sentence),
+    NoTree(Grammar, Sentence),
+    NoDerivation(TreeId)
 }
 
-#[derive(Debug)]
+impl fmt::Display for DomainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DomainError::Read { ref filename, ref message } =>
+                write!(f, "{}: {}", filename, message),
+            DomainError::Parse { ref filename, line, ref message } =>
+                write!(f, "{}:{}: {}", filename, line, message),
+            DomainError::BadToken { ref filename, line, ref token } =>
+                write!(f, "{}:{}: unknown token `{}`", filename, line, token),
+            DomainError::Unexpected(ref message) => write!(f, "{}", message),
+            DomainError::IllegalGrammar(g) => write!(f, "illegal grammar {}", g),
+            DomainError::NoTriggers(s) => write!(f, "no triggers for sentence {}", s),
+            DomainError::NoTriggerTable(ref name) => write!(f, "no trigger table `{}`", name),
+            DomainError::NoSurfaceForm(s) => write!(f, "no surface form for sentence {}", s),
+            DomainError::NoSentence(ref form) => write!(f, "no sentence `{}`", form),
+            DomainError::NoGenerators(s) => write!(f, "no grammar generates sentence {}", s),
+            DomainError::NoTree(g, s) => write!(f, "grammar {} doesn't generate sentence {}", g, s),
+            DomainError::NoDerivation(t) => write!(f, "no derivation for tree {}", t)
+        }
+    }
+}
+
+impl Error for DomainError {
+    fn description(&self) -> &str {
+        "error in language domain"
+    }
+}
+
+impl DomainError {
+    pub fn bad_token(filename: &str, line: u64, token: UnknownToken) -> Self {
+        DomainError::BadToken { filename: filename.to_string(), line: line, token: token.0 }
+    }
+
+    pub fn parse(filename: &str, line: u64, message: String) -> Self {
+        DomainError::Parse { filename: filename.to_string(), line: line, message: message }
+    }
+
+    pub fn csv(filename: &str, err: csv::Error) -> Self {
+        match err.position() {
+            Some(pos) => DomainError::parse(filename, pos.line(), err.to_string()),
+            None => DomainError::Read { filename: filename.to_string(), message: err.to_string() }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Trigger {
     On,
     Off,
@@ -37,220 +136,528 @@ pub enum Trigger {
     Irrelevant
 }
 
-type ColagTsvLine = (u16, u32, u32);
+impl TryFrom<char> for Trigger {
+    type Error = UnknownToken;
+    fn try_from(c: char) -> Result<Trigger, UnknownToken> {
+        match c {
+            '0' => Ok(Trigger::Off),
+            '1' => Ok(Trigger::On),
+            '*' => Ok(Trigger::Ambiguous),
+            '~' => Ok(Trigger::Irrelevant),
+            _ => Err(UnknownToken(c.to_string()))
+        }
+    }
+}
+
+impl Trigger {
+    /// The character this trigger is written as in trigger files.
+    pub fn symbol(&self) -> char {
+        match *self {
+            Trigger::Off => '0',
+            Trigger::On => '1',
+            Trigger::Ambiguous => '*',
+            Trigger::Irrelevant => '~'
+        }
+    }
+}
+
+/// Parses a trigger string like `~0*110~~0~*~~`, one character a parameter.
+pub fn parse_triggers(s: &str) -> Result<TriggerVec, UnknownToken> {
+    s.chars().map(Trigger::try_from).collect()
+}
+
+/// Returns `filename`, or a gzip or xz compressed copy of it if only that
+/// exists.
+pub fn find_data(filename: &str) -> String {
+    for compressed in [".gz", ".xz"].iter() {
+        let candidate = format!("{}{}", filename, compressed);
+        if !Path::new(filename).exists() && Path::new(&candidate).exists() {
+            return candidate;
+        }
+    }
+    filename.to_string()
+}
+
+// Opens `filename`, or a compressed copy of it.
+pub fn open(filename: &str) -> Result<File, DomainError> {
+    let found = find_data(filename);
+    File::open(&found)
+        .map_err(|e| DomainError::Read { filename: found, message: e.to_string() })
+}
+
+/// Wraps `source` in a decoder if it starts like a gzip or xz stream.
+pub fn decompress<'a, R: Read + 'a>(source: R) -> io::Result<Box<dyn Read + 'a>> {
+    let mut source = BufReader::new(source);
+    let (gzip, xz) = {
+        let start = source.fill_buf()?;
+        (start.starts_with(&[0x1f, 0x8b]), start.starts_with(b"\xfd7zXZ\x00"))
+    };
+    Ok(if gzip {
+        Box::new(MultiGzDecoder::new(source))
+    } else if xz {
+        Box::new(XzDecoder::new(source))
+    } else {
+        Box::new(source)
+    })
+}
+
+// Reads headerless `delimiter` separated fields from `source`, which may be
+// compressed. `name` identifies the source in errors.
+pub fn data_reader<'a, R: Read + 'a>(source: R, delimiter: u8, name: &str)
+                                     -> Result<csv::Reader<Box<dyn Read + 'a>>, DomainError> {
+    let source = decompress(source)
+        .map_err(|e| DomainError::Read { filename: name.to_string(), message: e.to_string() })?;
+    Ok(csv::ReaderBuilder::new()
+       .delimiter(delimiter)
+       .has_headers(false)
+       .from_reader(source))
+}
+
+// Opens a headerless data file of `delimiter` separated fields.
+pub fn open_data(filename: &str, delimiter: u8) -> Result<csv::Reader<Box<dyn Read>>, DomainError> {
+    data_reader(open(filename)?, delimiter, filename)
+}
+
+type ColagTsvLine = (u32, Sentence, TreeId);
 
 pub struct Colag {
+    num_params: usize,
     pub language: HashMap<Grammar, HashSet<u32>>,
     language_vec: HashMap<Grammar, Vec<Sentence>>,
     grammars: Vec<Grammar>,
-    trigger: HashMap<Sentence, TriggerVec>,
-    surface_form: HashMap<Sentence, SurfaceForm>
+    sentences: Vec<Sentence>,
+    // trigger tables by name, `NORMAL` being the one `triggers` looks in,
+    // shared with the learners that use them
+    trigger: HashMap<String, Arc<TriggerMap>>,
+    surface_form: HashMap<Sentence, SurfaceForm>,
+    // sentences by their displayed surface form, and the reverse of `language`
+    by_surface_form: HashMap<String, Sentence>,
+    generators: HashMap<Sentence, Vec<Grammar>>,
+    // `language` as a grammar x sentence bit matrix, rows in the order of
+    // `grammars` and columns in the order of `sentences`
+    grammar_index: DenseIndex,
+    sentence_index: DenseIndex,
+    parses: BitMatrix,
+    // the structure each grammar assigns each sentence it generates
+    tree: HashMap<(Grammar, Sentence), TreeId>,
+    derivation: HashMap<TreeId, Tree>
 }
 
 impl LanguageDomain for Colag {
-    fn language(&self, g: &Grammar) -> Result<&HashSet<Sentence>, IllegalGrammar> {
-        self.language.get(g).ok_or_else({|| IllegalGrammar {grammar: *g } })
+    fn language(&self, g: &Grammar) -> Result<&HashSet<Sentence>, DomainError> {
+        self.language.get(g).ok_or_else(|| DomainError::IllegalGrammar(*g))
     }
-    fn language_vec(&self, g: &Grammar) -> Result<&Vec<Sentence>, IllegalGrammar> {
-        self.language_vec.get(g).ok_or_else({|| IllegalGrammar {grammar: *g } })
+    fn language_vec(&self, g: &Grammar) -> Result<&Vec<Sentence>, DomainError> {
+        self.language_vec.get(g).ok_or_else(|| DomainError::IllegalGrammar(*g))
     }
-    fn triggers(&self, s: &Sentence) -> &TriggerVec {
-        self.trigger.get(s).unwrap()
+    fn triggers(&self, s: &Sentence) -> Result<&TriggerVec, DomainError> {
+        self.trigger.get(NORMAL)
+            .and_then(|table| table.sentence(s))
+            .ok_or_else(|| DomainError::NoTriggers(*s))
     }
-    fn parses(&self, g: &Grammar, s: &Sentence) -> Result<bool, IllegalGrammar> {
-        match self.language.get(g) {
-            None => Err(IllegalGrammar{ grammar: *g }),
-            Some(sents) => Ok(sents.contains(s))
-        }
+    fn parses(&self, g: &Grammar, s: &Sentence) -> Result<bool, DomainError> {
+        let row = self.grammar_index.get(g.0).ok_or(DomainError::IllegalGrammar(*g))?;
+        Ok(self.sentence_index.get(*s).map_or(false, |col| self.parses.get(row, col)))
     }
-    fn surface_form(&self, s: &Sentence) -> &SurfaceForm {
-        self.surface_form.get(s).unwrap()
+    fn surface_form(&self, s: &Sentence) -> Result<&SurfaceForm, DomainError> {
+        self.surface_form.get(s).ok_or_else(|| DomainError::NoSurfaceForm(*s))
     }
     fn random_grammar<T: Rng>(&self, rng: &mut T) -> &Grammar {
         rng.choose(&self.grammars).unwrap()
     }
+    fn grammars(&self) -> &Vec<Grammar> {
+        &self.grammars
+    }
+    fn num_params(&self) -> usize {
+        self.num_params
+    }
 }
 
 impl Colag {
     pub fn new() -> Colag {
         let lang = HashMap::new();
-        Colag { language: lang,
+        Colag { num_params: 0,
+                language: lang,
                 language_vec: HashMap::new(),
                 grammars: Vec::new(),
+                sentences: Vec::new(),
                 trigger: HashMap::new(),
-                surface_form: HashMap::new()
+                surface_form: HashMap::new(),
+                by_surface_form: HashMap::new(),
+                generators: HashMap::new(),
+                grammar_index: DenseIndex::new(&[]),
+                sentence_index: DenseIndex::new(&[]),
+                parses: BitMatrix::new(0, 0),
+                tree: HashMap::new(),
+                derivation: HashMap::new()
+        }
+    }
+
+    /// Loads COLAG from the files in `./data`, by way of a snapshot that's
+    /// rebuilt whenever they change.
+    pub fn load() -> Result<Colag, DomainError> {
+        let exists = |f: &str| Path::new(&find_data(f)).exists();
+        let tables: Vec<&(&str, &str)> = COLAG_TRIGGERS.iter()
+            .enumerate()
+            .filter(|&(n, &(_, f))| n == 0 || exists(f))
+            .map(|(_, table)| table)
+            .collect();
+        let mut sources: Vec<String> = vec![find_data(COLAG_IDS), find_data(COLAG_SENTENCES)];
+        sources.extend(tables.iter().map(|&&(_, f)| find_data(f)));
+        // the derivations aren't part of the COLAG release, so they're optional
+        let trees = exists(COLAG_TREES);
+        if trees {
+            sources.push(find_data(COLAG_TREES));
         }
+        let sources: Vec<&str> = sources.iter().map(|f| f.as_str()).collect();
+        if let Some(domain) = snapshot::read(COLAG_SNAPSHOT, &sources) {
+            return Ok(domain);
+        }
+
+        let mut domain = Colag::from_file(COLAG_IDS, COLAG_PARAMS)?
+            .read_surface_forms(COLAG_SENTENCES)?;
+        for &&(name, filename) in tables.iter() {
+            domain = domain.read_triggers(name, filename)?;
+        }
+        if trees {
+            domain = domain.read_trees(COLAG_TREES)?;
+        }
+        domain.check_colag()?;
+        if let Err(e) = snapshot::write(COLAG_SNAPSHOT, &sources, &domain) {
+            eprintln!("Couldn't write {}: {}", COLAG_SNAPSHOT, e);
+        }
+        Ok(domain)
     }
 
     pub fn default() -> Colag {
-        Colag::from_file("./data/COLAG_2011_ids.txt")
-            .unwrap()
-            .read_triggers("./data/irrelevance-output.txt")
-            .unwrap()
-            .read_surface_forms("./data/COLAG_2011_sents.txt")
-            .unwrap()
-    }
-
-    pub fn random_weighted_grammar<T: Rng>(rng: &mut T,
-                                   weights: &[f64; NUM_PARAMS]) -> Grammar {
-        let mut grammar = 0;
-        for param in 0..NUM_PARAMS {
+        Colag::load().unwrap_or_else(|e| panic!("Couldn't load Colag: {}", e))
+    }
+
+    /// COLAG for the benches, or None where `./data` doesn't have it, so that
+    /// `cargo test` skips them rather than failing.
+    #[cfg(test)]
+    pub fn for_bench() -> Option<Colag> {
+        if Path::new(&find_data(COLAG_IDS)).exists() {
+            Some(Colag::default())
+        } else {
+            None
+        }
+    }
+
+    // Sanity checks that what we loaded is really COLAG.
+    fn check_colag(&self) -> Result<(), DomainError> {
+        let unexpected = |message: String| Err(DomainError::Unexpected(message));
+        if self.num_params != COLAG_PARAMS {
+            return unexpected(format!("Expected {} parameters in Colag, saw {}",
+                                      COLAG_PARAMS, self.num_params));
+        }
+        if self.language.len() != 3072 {
+            return unexpected(format!("Expected 3072 languages in Colag, saw {}",
+                                      self.language.len()));
+        }
+        let english = self.language(&Grammar(611))?;
+        if english.len() != 360 {
+            return unexpected(format!("Expected 360 sentences in Colag English, saw {}",
+                                      english.len()));
+        }
+        for s in vec![3138, 1970, 5871, 6923, 1969].iter() {
+            if !english.contains(s) {
+                return unexpected(format!("Expected sentence {} in Colag English", s));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn random_weighted_grammar<T: Rng>(rng: &mut T, weights: &[f64]) -> Grammar {
+        let mut grammar = Grammar(0);
+        for param in 0..weights.len() {
             if weighted_coin_flip(rng, weights[param]) {
-                grammar = set_param(grammar, param);
+                grammar = grammar.set(param, weights.len());
             }
         }
         grammar
     }
 
-    pub fn from_file(filename: &str) -> Result<Colag, Box<Error>> {
-        let mut rdr = csv::ReaderBuilder::new()
-            .delimiter(b'\t')
-            .has_headers(false)
-            .from_path(filename)
-            .expect(filename);
+    /// Builds a domain of `num_params` parameters out of the language of each
+    /// legal grammar.
+    pub fn from_languages(num_params: usize, language: HashMap<Grammar, HashSet<Sentence>>) -> Colag {
+        assert!(num_params <= 32, "Grammars can't have more than 32 parameters");
         let mut domain = Colag::new();
+        domain.num_params = num_params;
+        domain.language = language;
 
-        for result in rdr.deserialize() {
-            let (grammar, sentence, _tree): ColagTsvLine = result?;
-            if domain.language.contains_key(&grammar){
-                domain.language.get_mut(&grammar).map(|set| set.insert(sentence));
-            } else {
-                let mut set = HashSet::new();
-                set.insert(sentence);
-                domain.language.insert(grammar, set);
-            }
-        }
-
+        // in order, so that seeded runs pick the same sentences every time
         for (grammar, sentences) in domain.language.iter() {
-            domain.language_vec.insert(*grammar,
-                                       sentences.clone().into_iter().collect());
+            let mut sentences: Vec<Sentence> = sentences.iter().cloned().collect();
+            sentences.sort();
+            domain.language_vec.insert(*grammar, sentences);
         }
 
         domain.grammars = domain.language.keys().map(|x| *x).collect();
-        assert!(domain.language.len() == 3072, "Expected 3072 languages in Colag");
-        assert!(domain.language_vec.len() == 3072, "Expected 3072 languages in Colag");
-        {
-            let english = domain.language.get(&611).unwrap();
-            assert!(english.len() == 360, "Expected 360 sentences in Colag English");
-            for s in vec![3138, 1970, 5871, 6923, 1969].iter() {
-                assert!(english.contains(&s), format!("Expected sentence {} in Colag English", &s))
+        domain.grammars.sort();
+        for grammar in domain.grammars.iter() {
+            for sentence in domain.language[grammar].iter() {
+                domain.generators.entry(*sentence).or_insert_with(Vec::new).push(*grammar);
             }
         }
-        Ok(domain)
+        domain.sentences = domain.generators.keys().cloned().collect();
+        domain.sentences.sort();
+
+        domain.grammar_index = DenseIndex::new(&domain.grammars.iter().map(|g| g.0).collect::<Vec<_>>());
+        domain.sentence_index = DenseIndex::new(&domain.sentences);
+        domain.parses = BitMatrix::new(domain.grammars.len(), domain.sentences.len());
+        for (row, grammar) in domain.grammars.iter().enumerate() {
+            for sentence in domain.language[grammar].iter() {
+                let col = domain.sentence_index.get(*sentence).unwrap();
+                domain.parses.set(row, col);
+            }
+        }
+        domain
     }
 
-    pub fn read_triggers(mut self, filename: &str) -> Result<Self, Box<Error>> {
-        let mut rdr = csv::ReaderBuilder::new()
-            .delimiter(b' ')
-            .has_headers(false)
-            .from_path(filename)
-            .expect(filename);
-
-        for result in rdr.deserialize() {
-            let (sentence, trigger_str): (Sentence, String) = result?;
-            assert!(trigger_str.len() == NUM_PARAMS);
-            let mut trigger_vec: Vec<Trigger> = trigger_str
-                .as_bytes()
-                .iter()
-                .map(|b| {
-                    match *b {
-                        b'0' => Trigger::Off,
-                        b'1' => Trigger::On,
-                        b'*' => Trigger::Ambiguous,
-                        b'~' => Trigger::Irrelevant,
-                        _ => panic!("illegal char in irrel str")
-                    }
-                }).collect();
-
-            unsafe {
-                let mut array: TriggerVec = mem::uninitialized();
-                for i in 0..NUM_PARAMS {
-                    array[i] = trigger_vec.remove(0);
-                }
-                self.trigger.insert(sentence, array);
+    /// Reads a tab-separated `grammar sentence tree` file of grammars of
+    /// `num_params` parameters.
+    pub fn from_file(filename: &str, num_params: usize) -> Result<Colag, DomainError> {
+        Colag::from_reader(open(filename)?, filename, num_params)
+    }
+
+    /// Reads `grammar sentence tree` lines from `source`, which may be
+    /// compressed. `name` identifies it in errors.
+    pub fn from_reader<R: Read>(source: R, name: &str,
+                                num_params: usize) -> Result<Colag, DomainError> {
+        if num_params > grammar::MAX_PARAMS {
+            return Err(DomainError::Unexpected(
+                format!("Grammars can't have more than {} parameters, not {}",
+                        grammar::MAX_PARAMS, num_params)));
+        }
+        let mut rdr = data_reader(source, b'\t', name)?;
+        let mut language: HashMap<Grammar, HashSet<Sentence>> = HashMap::new();
+        let mut trees: HashMap<(Grammar, Sentence), TreeId> = HashMap::new();
+
+        for result in rdr.records() {
+            let record = result.map_err(|e| DomainError::csv(name, e))?;
+            let line = record.position().map_or(0, |p| p.line());
+            let (grammar, sentence, tree): ColagTsvLine = record.deserialize(None)
+                .map_err(|e| DomainError::parse(name, line, e.to_string()))?;
+            let grammar = Grammar(grammar);
+            if !grammar.fits(num_params) {
+                return Err(DomainError::parse(name, line, format!(
+                    "grammar {} doesn't fit in {} parameters", grammar, num_params)));
             }
+            language.entry(grammar).or_insert_with(HashSet::new).insert(sentence);
+            trees.insert((grammar, sentence), tree);
         }
+
+        let mut domain = Colag::from_languages(num_params, language);
+        domain.tree = trees;
+        Ok(domain)
+    }
+
+    /// Reads `filename` into the trigger table called `table`.
+    pub fn read_triggers(mut self, table: &str, filename: &str) -> Result<Self, DomainError> {
+        let triggers = TriggerMap::from_file(filename, self.num_params)?;
+        self.add_trigger_table(table, triggers);
         Ok(self)
     }
 
-    fn sentence_generators(&self) -> HashMap<&Sentence, &Grammar> {
-        unimplemented!();
+    pub fn add_trigger_table(&mut self, table: &str, triggers: TriggerMap) {
+        self.trigger.insert(table.to_string(), Arc::new(triggers));
     }
 
-    fn all_sentences(&self) -> HashSet<Sentence> {
-        let mut all_sents: HashSet<Sentence> = HashSet::new();
-        for sents in self.language.values() {
-            all_sents.extend(sents);
-        }
-        all_sents
+    /// The trigger table called `table`, eg. `NORMAL` or `NO_EQUIV`.
+    pub fn trigger_table(&self, table: &str) -> Result<&TriggerMap, DomainError> {
+        self.shared_trigger_table(table).map(|t| &**t)
+    }
+
+    /// The trigger table called `table`, for keeping hold of without
+    /// borrowing the domain.
+    pub fn shared_trigger_table(&self, table: &str) -> Result<&Arc<TriggerMap>, DomainError> {
+        self.trigger.get(table).ok_or_else(|| DomainError::NoTriggerTable(table.to_string()))
+    }
+
+    /// The names of the trigger tables, in order.
+    pub fn trigger_tables(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.trigger.keys().map(|n| n.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    /// The grammars that generate each sentence, in order.
+    pub fn sentence_generators(&self) -> &HashMap<Sentence, Vec<Grammar>> {
+        &self.generators
+    }
+
+    /// The grammars that generate `sentence`, in order.
+    pub fn generators(&self, sentence: &Sentence) -> Result<&Vec<Grammar>, DomainError> {
+        self.generators.get(sentence)
+            .ok_or_else(|| DomainError::NoGenerators(*sentence))
+    }
+
+    /// Looks up a sentence by its surface form, written like `Q S[+WH] Aux
+    /// Verb` (a leading DEC can be left off), and returns its id and the
+    /// grammars that generate it.
+    pub fn lookup(&self, surface_form: &str) -> Result<(Sentence, &Vec<Grammar>), DomainError> {
+        let no_sentence = || DomainError::NoSentence(surface_form.trim().to_string());
+        let form = SurfaceForm::parse_with_illoc(surface_form).map_err(|_| no_sentence())?;
+        let sentence = *self.by_surface_form.get(&form.to_string()).ok_or_else(no_sentence)?;
+        Ok((sentence, self.generators(&sentence)?))
+    }
+
+    /// Every sentence of every language in the domain.
+    pub fn all_sentences(&self) -> &Vec<Sentence> {
+        &self.sentences
     }
 
+    // Ok(value) if every grammar that generates `sent` sets `param` to value,
+    // otherwise the generators.
     fn unambiguous_trigger(&self, sent: &Sentence, param: usize) -> Result<bool, Vec<Grammar>> {
-        unimplemented!();
+        let generators = self.generators.get(sent).cloned().unwrap_or_default();
+        let on = generators.iter()
+            .filter(|g| g.get(param, self.num_params))
+            .count();
+        if on == generators.len() {
+            Ok(true)
+        } else if on == 0 {
+            Ok(false)
+        } else {
+            Err(generators)
+        }
     }
 
     fn illegal_grammar(&self, g: &Grammar) -> bool {
-        unimplemented!();
+        self.grammar_index.get(g.0).is_none()
     }
 
     fn ambig_or_irrel(&self, generators: Vec<Grammar>, param: usize) -> Trigger {
         for generator in generators.iter() {
-            let min_pair = toggled(&generator, param);
-            if !generators.contains(&&min_pair) && !self.illegal_grammar(&&min_pair){
+            let min_pair = generator.toggle(param, self.num_params);
+            if !generators.contains(&min_pair) && !self.illegal_grammar(&min_pair) {
                 return Trigger::Ambiguous
             }
         }
         Trigger::Irrelevant
     }
 
+    /// Works out the `NORMAL` trigger table from the languages.
     pub fn gen_triggers(&mut self) {
-        let sentences = self.all_sentences().into_iter();
-        for sentence in sentences {
-            let mut triggers = unsafe {
-                let mut triggers: TriggerVec = mem::uninitialized();
-                for param in 0..NUM_PARAMS {
-                    triggers[param] = match self.unambiguous_trigger(&sentence, param) {
-                        Ok(true) => Trigger::On,
-                        Ok(false) => Trigger::Off,
-                        Err(generators) => self.ambig_or_irrel(generators, param)
-                    };
-                }
-                triggers
-            };
-            self.trigger.insert(sentence, triggers);
-        }
-    }
-
-    pub fn read_surface_forms(mut self, filename: &str) -> Result<Self, Box<Error>> {
-        let mut rdr = csv::ReaderBuilder::new()
-            .delimiter(b'\t')
-            .has_headers(false)
-            .from_path(filename)
-            .expect(filename);
-
-        for result in rdr.deserialize() {
-            let (sentence, illoc, form): (Sentence, String, String) = result?;
-            let illoc: Illoc = illoc.trim().into();
-            let mut form: SurfaceForm = form.trim().into();
+        let mut table = TriggerMap::new();
+        for &sentence in self.all_sentences().iter() {
+            let triggers: TriggerVec = (0..self.num_params)
+                .map(|param| match self.unambiguous_trigger(&sentence, param) {
+                    Ok(true) => Trigger::On,
+                    Ok(false) => Trigger::Off,
+                    Err(generators) => self.ambig_or_irrel(generators, param)
+                })
+                .collect();
+            table.insert(sentence, triggers);
+        }
+        self.add_trigger_table(NORMAL, table);
+    }
+
+    /// The sentences that have surface forms.
+    pub fn surface_forms<'a>(&'a self) -> Box<dyn Iterator<Item=&'a Sentence> + 'a> {
+        Box::new(self.surface_form.keys())
+    }
+
+    pub fn add_surface_form(&mut self, sentence: Sentence, form: SurfaceForm) {
+        self.by_surface_form.insert(form.to_string(), sentence);
+        self.surface_form.insert(sentence, form);
+    }
+
+    pub fn read_surface_forms(self, filename: &str) -> Result<Self, DomainError> {
+        self.read_surface_forms_from(open(filename)?, filename)
+    }
+
+    pub fn read_surface_forms_from<R: Read>(mut self, source: R, name: &str) -> Result<Self, DomainError> {
+        let mut rdr = data_reader(source, b'\t', name)?;
+
+        for result in rdr.records() {
+            let record = result.map_err(|e| DomainError::csv(name, e))?;
+            let line = record.position().map_or(0, |p| p.line());
+            let (sentence, illoc, form): (Sentence, String, String) = record.deserialize(None)
+                .map_err(|e| DomainError::parse(name, line, e.to_string()))?;
+            let illoc = Illoc::try_from(illoc.trim())
+                .map_err(|t| DomainError::bad_token(name, line, t))?;
+            let mut form = SurfaceForm::try_from(form.trim())
+                .map_err(|t| DomainError::bad_token(name, line, t))?;
             form.illoc = illoc;
-            self.surface_form.insert(sentence, form);
+            self.add_surface_form(sentence, form);
         }
         Ok(self)
     }
-}
 
-fn toggled(grammar: &Grammar, param_num: usize) -> Grammar {
-    unimplemented!();
-}
+    /// Reads a tab-separated `tree derivation` file, derivations bracketed
+    /// like `(CP (Spec S) (C' (C Verb) O1))`.
+    pub fn read_trees(self, filename: &str) -> Result<Self, DomainError> {
+        self.read_trees_from(open(filename)?, filename)
+    }
 
-/// Returns parameter # `param_num` from `grammar`.
-pub fn get_param(grammar: &Grammar, param_num: usize) -> Grammar {
-    (grammar >> (NUM_PARAMS - param_num - 1)) & 1
-}
+    pub fn read_trees_from<R: Read>(mut self, source: R, name: &str) -> Result<Self, DomainError> {
+        let mut rdr = data_reader(source, b'\t', name)?;
+
+        for result in rdr.records() {
+            let record = result.map_err(|e| DomainError::csv(name, e))?;
+            let line = record.position().map_or(0, |p| p.line());
+            let (id, derivation): (TreeId, String) = record.deserialize(None)
+                .map_err(|e| DomainError::parse(name, line, e.to_string()))?;
+            let derivation = Tree::try_from(derivation.trim())
+                .map_err(|t| DomainError::bad_token(name, line, t))?;
+            self.derivation.insert(id, derivation);
+        }
+        Ok(self)
+    }
 
-/// Returns `grammar` with `param_num` turned on.
-fn set_param(grammar: Grammar, param_num: usize) -> Grammar {
-    grammar + (1 << (NUM_PARAMS - param_num - 1))
+    pub fn add_tree(&mut self, grammar: Grammar, sentence: Sentence, tree: TreeId) {
+        self.tree.insert((grammar, sentence), tree);
+    }
+
+    pub fn add_derivation(&mut self, tree: TreeId, derivation: Tree) {
+        self.derivation.insert(tree, derivation);
+    }
+
+    pub fn derivations(&self) -> &HashMap<TreeId, Tree> {
+        &self.derivation
+    }
+
+    /// The id of the structure `grammar` assigns `sentence`.
+    pub fn tree(&self, grammar: &Grammar, sentence: &Sentence) -> Result<TreeId, DomainError> {
+        self.tree.get(&(*grammar, *sentence))
+            .cloned()
+            .ok_or_else(|| DomainError::NoTree(*grammar, *sentence))
+    }
+
+    /// The derivation of `sentence` under `grammar`.
+    pub fn derivation(&self, grammar: &Grammar, sentence: &Sentence) -> Result<&Tree, DomainError> {
+        let id = self.tree(grammar, sentence)?;
+        self.derivation.get(&id).ok_or(DomainError::NoDerivation(id))
+    }
+
+    /// Every (grammar, sentence) pair with the same structure as `sentence`
+    /// under `grammar`, itself included, in order.
+    pub fn same_structure(&self, grammar: &Grammar,
+                          sentence: &Sentence) -> Result<Vec<(Grammar, Sentence)>, DomainError> {
+        let id = self.tree(grammar, sentence)?;
+        let mut pairs: Vec<(Grammar, Sentence)> = self.tree.iter()
+            .filter(|&(_, t)| *t == id)
+            .map(|(pair, _)| *pair)
+            .collect();
+        pairs.sort();
+        Ok(pairs)
+    }
+
+    /// The (grammar, sentence) pairs of each structure shared by more than
+    /// one pair.
+    pub fn shared_structures(&self) -> HashMap<TreeId, Vec<(Grammar, Sentence)>> {
+        let mut shared: HashMap<TreeId, Vec<(Grammar, Sentence)>> = HashMap::new();
+        for (pair, id) in self.tree.iter() {
+            shared.entry(*id).or_insert_with(Vec::new).push(*pair);
+        }
+        shared.retain(|_, pairs| pairs.len() > 1);
+        for pairs in shared.values_mut() {
+            pairs.sort();
+        }
+        shared
+    }
 }
 
 /// Returns true `weight` percent of the time
@@ -260,26 +667,189 @@ fn weighted_coin_flip<T: Rng>(rng: &mut T, weight: f64) -> bool {
     range.sample(rng) < weight
 }
 
+#[cfg(test)]
 mod bench {
     extern crate test;
-    use domain::{LanguageDomain, Colag, NUM_PARAMS};
+    use domain::{LanguageDomain, Colag};
     use self::test::Bencher;
     use rand;
+    use rand::Rng;
 
     #[bench]
     fn random_grammar(b: &mut Bencher) {
-        let colag = Colag::default();
+        let colag = match Colag::for_bench() {
+            Some(colag) => colag,
+            None => return
+        };
         let ref mut rng = rand::weak_rng();
         b.iter(|| colag.random_grammar(rng));
     }
 
 
+    #[bench]
+    fn parses(b: &mut Bencher) {
+        let colag = match Colag::for_bench() {
+            Some(colag) => colag,
+            None => return
+        };
+        let ref mut rng = rand::weak_rng();
+        let sentences = colag.all_sentences();
+        b.iter(|| {
+            let g = colag.random_grammar(rng);
+            let s = rng.choose(sentences).unwrap();
+            colag.parses(g, s)
+        });
+    }
+
+    #[bench]
+    fn language_contains(b: &mut Bencher) {
+        let colag = match Colag::for_bench() {
+            Some(colag) => colag,
+            None => return
+        };
+        let ref mut rng = rand::weak_rng();
+        let sentences = colag.all_sentences();
+        b.iter(|| {
+            let g = colag.random_grammar(rng);
+            let s = rng.choose(sentences).unwrap();
+            colag.language[g].contains(s)
+        });
+    }
+
     #[bench]
     fn random_weighted_grammar(b: &mut Bencher) {
-        let colag = Colag::default();
+        let colag = match Colag::for_bench() {
+            Some(colag) => colag,
+            None => return
+        };
         let ref mut rng = rand::weak_rng();
-        let ref weights = [0.5; NUM_PARAMS];
+        let ref weights = vec![0.5; colag.num_params()];
         b.iter(|| Colag::random_weighted_grammar(rng, weights));
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::collections::{HashMap, HashSet};
+    use std::fs::File;
+    use std::io::{Cursor, Read, Write};
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use xz2::write::XzEncoder;
+    use domain::{Colag, LanguageDomain, Trigger, DomainError, Grammar, Sentence, decompress,
+                 find_data};
+    use triggers::TriggerMap;
+
+    const IDS: &'static str = "0\t1\t0\n0\t2\t1\n3\t2\t2\n3\t3\t3\n";
+    const SENTS: &'static str = "1\tDEC\tS Verb\n2\tQ\tS[+WH] Verb O1\n3\tIMP\tVerb O1\n";
+    const TRIGGERS: &'static str = "1 00\n2 **\n3 11\n";
+
+    fn gzip(data: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn xz(data: &str) -> Vec<u8> {
+        let mut encoder = XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(data.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    // Loads the fixture with each file packed by `pack`.
+    fn load<F: Fn(&str) -> Vec<u8>>(pack: F) -> Colag {
+        let mut domain = Colag::from_reader(Cursor::new(pack(IDS)), "ids", 2).unwrap()
+            .read_surface_forms_from(Cursor::new(pack(SENTS)), "sents").unwrap();
+        let table = TriggerMap::from_reader(Cursor::new(pack(TRIGGERS)), "triggers", 2).unwrap();
+        domain.add_trigger_table("normal", table);
+        domain
+    }
+
+    #[test]
+    fn loads_plain_and_compressed_data() {
+        let packers: [fn(&str) -> Vec<u8>; 3] = [|s| s.as_bytes().to_vec(), gzip, xz];
+        for pack in packers.iter() {
+            let domain = load(pack);
+            assert_eq!(domain.grammars(), &vec![Grammar(0), Grammar(3)]);
+            assert_eq!(domain.language_vec(&Grammar(3)).unwrap(), &vec![2, 3]);
+            assert_eq!(domain.tree(&Grammar(3), &3).unwrap(), 3);
+            assert_eq!(domain.surface_form(&2).unwrap().to_string(), "Q S[+WH] Verb O1");
+            assert_eq!(domain.lookup("IMP Verb O1").unwrap().0, 3);
+            assert_eq!(domain.triggers(&3).unwrap(), &vec![Trigger::On, Trigger::On]);
+            assert_eq!(domain.triggers(&2).unwrap(),
+                       &vec![Trigger::Ambiguous, Trigger::Ambiguous]);
+        }
+    }
+
+    // Seeded runs draw grammars and sentences by index, so these orders must
+    // not depend on the order of the hash maps they're built from.
+    #[test]
+    fn grammars_and_sentences_are_kept_in_order() {
+        for n in 1..20 {
+            let language: HashMap<Grammar, HashSet<Sentence>> = (0..n)
+                .map(|g| (Grammar((g * 7) % 32), (0..n).map(|s| (s * 13 + g) % 50).collect()))
+                .collect();
+            let domain = Colag::from_languages(5, language.clone());
+            let mut grammars: Vec<Grammar> = language.keys().cloned().collect();
+            grammars.sort();
+            assert_eq!(domain.grammars(), &grammars);
+            assert!(domain.all_sentences().windows(2).all(|w| w[0] < w[1]));
+            for g in grammars.iter() {
+                assert!(domain.language_vec(g).unwrap().windows(2).all(|w| w[0] < w[1]));
+            }
+        }
+    }
+
+    #[test]
+    fn reports_where_bad_data_is() {
+        match Colag::from_reader(Cursor::new(gzip("0\t1\t0\n4\t1\t1\n")), "ids", 2) {
+            Err(DomainError::Parse { line: 2, .. }) => (),
+            other => panic!("expected an error on line 2, got {:?}", other.err())
+        }
+        match TriggerMap::from_reader(Cursor::new(xz("1 00\n2 0\n")), "triggers", 2) {
+            Err(DomainError::Parse { line: 2, .. }) => (),
+            other => panic!("expected an error on line 2, got {:?}", other.err().map(|e| e.to_string()))
+        }
+    }
+
+    #[test]
+    fn detects_compression_by_magic_number() {
+        let decoded = |data: Vec<u8>| {
+            let mut bytes = Vec::new();
+            decompress(Cursor::new(data)).unwrap().read_to_end(&mut bytes).unwrap();
+            bytes
+        };
+        assert_eq!(&gzip(IDS)[..2], &[0x1f, 0x8b]);
+        assert_eq!(&xz(IDS)[..6], b"\xfd7zXZ\x00");
+        assert_eq!(decoded(gzip(IDS)), IDS.as_bytes());
+        assert_eq!(decoded(xz(IDS)), IDS.as_bytes());
+        assert_eq!(decoded(IDS.as_bytes().to_vec()), IDS.as_bytes());
+        assert_eq!(decoded(Vec::new()), b"");
+        // too short to be either, and a near miss, pass through untouched
+        assert_eq!(decoded(vec![0x1f]), &[0x1f]);
+        assert_eq!(decoded(b"\xfd7zXY\x00".to_vec()), b"\xfd7zXY\x00");
+    }
+
+    #[test]
+    fn finds_and_reads_compressed_copies_of_files() {
+        let base = env::temp_dir().join(format!("domain-ids-{}.txt", ::std::process::id()));
+        let base = base.to_str().unwrap();
+        let gz = format!("{}.gz", base);
+        fs::write(&gz, gzip(IDS)).unwrap();
+        assert_eq!(find_data(base), gz);
+        let domain = Colag::from_file(base, 2).unwrap();
+        assert_eq!(domain.grammars(), &vec![Grammar(0), Grammar(3)]);
+
+        // the plain file wins once there is one
+        fs::write(base, IDS).unwrap();
+        assert_eq!(find_data(base), base);
+        let mut s = String::new();
+        decompress(File::open(base).unwrap()).unwrap().read_to_string(&mut s).unwrap();
+        assert_eq!(s, IDS);
+        fs::remove_file(base).unwrap();
+        fs::remove_file(&gz).unwrap();
+    }
+}
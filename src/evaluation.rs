@@ -0,0 +1,121 @@
+use std::fmt;
+use std::collections::HashMap;
+
+use domain::{LanguageDomain, Grammar};
+use hypothesis::Hypothesis;
+use param;
+use relations;
+use relations::{Relation, RELATIONS};
+
+// How far a learner's guess is from its target.
+pub struct Evaluation {
+    pub guess: Grammar,
+    // the parameters the guess sets differently from the target, in order
+    pub wrong: Vec<usize>,
+    // from the learner's beliefs to the target's settings (Hypothesis::distance)
+    pub distance: f64,
+    // how the language of the guess stands to the target's, or None if the
+    // guess isn't a legal grammar
+    pub relation: Option<Relation>,
+    num_params: usize
+}
+
+impl Evaluation {
+    pub fn new<D: LanguageDomain>(domain: &D, target: Grammar, guess: Grammar,
+                                  hypothesis: &dyn Hypothesis) -> Self {
+        let num_params = domain.num_params();
+        Evaluation {
+            guess,
+            wrong: guess.differences(&target, num_params),
+            distance: hypothesis.distance(&target),
+            relation: relations::relation(domain, &guess, &target).ok(),
+            num_params
+        }
+    }
+
+    /// The number of parameters the guess got wrong.
+    pub fn hamming(&self) -> usize {
+        self.wrong.len()
+    }
+}
+
+// hamming, wrong parameters, distance, relation
+impl fmt::Display for Evaluation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let wrong: Vec<String> = self.wrong.iter()
+            .map(|&p| param::label(p, self.num_params))
+            .collect();
+        write!(f, "{}, {}, {:.4}, ", self.hamming(),
+               if wrong.is_empty() { "none".to_string() } else { wrong.join("+") },
+               self.distance)?;
+        match self.relation {
+            Some(relation) => write!(f, "{}", relation),
+            None => write!(f, "illegal")
+        }
+    }
+}
+
+// The evaluations of many trials of one learner, accumulated.
+pub struct Summary {
+    pub trials: usize,
+    hamming: usize,
+    distance: f64,
+    // the number of trials that got each parameter wrong
+    wrong: Vec<usize>,
+    // the number of trials in each relation to the target, None for illegal
+    // guesses
+    relations: HashMap<Option<Relation>, usize>
+}
+
+impl Summary {
+    pub fn new(num_params: usize) -> Self {
+        Summary { trials: 0, hamming: 0, distance: 0., wrong: vec![0; num_params],
+                  relations: HashMap::new() }
+    }
+
+    pub fn add(&mut self, evaluation: &Evaluation) {
+        self.trials += 1;
+        self.hamming += evaluation.hamming();
+        self.distance += evaluation.distance;
+        for &param in evaluation.wrong.iter() {
+            self.wrong[param] += 1;
+        }
+        *self.relations.entry(evaluation.relation).or_insert(0) += 1;
+    }
+
+    pub fn mean_hamming(&self) -> f64 {
+        self.hamming as f64 / self.trials as f64
+    }
+
+    pub fn mean_distance(&self) -> f64 {
+        self.distance / self.trials as f64
+    }
+
+    /// The share of trials that got `param` wrong.
+    pub fn error_rate(&self, param: usize) -> f64 {
+        self.wrong[param] as f64 / self.trials as f64
+    }
+
+    /// The share of trials whose guess stood in `relation` to the target, or
+    /// was illegal for None.
+    pub fn share(&self, relation: Option<Relation>) -> f64 {
+        self.relations.get(&relation).cloned().unwrap_or(0) as f64 / self.trials as f64
+    }
+}
+
+// trials, mean hamming, mean distance, the share of each relation and of
+// illegal guesses, then each parameter's error rate like `SP=0.010, `
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, {:.3}, {:.4}, ", self.trials, self.mean_hamming(), self.mean_distance())?;
+        for relation in RELATIONS.iter() {
+            write!(f, "{}={:.3}, ", relation, self.share(Some(*relation)))?;
+        }
+        write!(f, "illegal={:.3}", self.share(None))?;
+        let num_params = self.wrong.len();
+        for param in 0..num_params {
+            write!(f, ", {}={:.3}", param::label(param, num_params), self.error_rate(param))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+use std::str::FromStr;
+use std::num::ParseIntError;
+
+/// The most parameters a grammar can have.
+pub const MAX_PARAMS: usize = 32;
+
+// A grammar of `num_params` parameters, one bit each. Parameter 0 is the most
+// significant of the `num_params` bits; in COLAG that's `Param::SP` (see
+// `Param::index`). A domain's grammars all share its `num_params`, so it isn't
+// kept in every grammar: the operations take it and check it in debug builds.
+// Grammars are values, so the setters return a new grammar rather than
+// changing this one. They're read and written as plain numbers, like 611 for
+// COLAG English.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Grammar(pub u32);
+
+fn mask(param: usize, num_params: usize) -> u32 {
+    debug_assert!(param < num_params, "No parameter {} of {}", param, num_params);
+    1 << (num_params - param - 1)
+}
+
+impl Grammar {
+    /// True if `param` is set.
+    pub fn get(&self, param: usize, num_params: usize) -> bool {
+        self.0 & mask(param, num_params) != 0
+    }
+
+    /// This grammar with `param` turned on.
+    pub fn set(&self, param: usize, num_params: usize) -> Grammar {
+        Grammar(self.0 | mask(param, num_params))
+    }
+
+    /// This grammar with `param` turned off.
+    pub fn clear(&self, param: usize, num_params: usize) -> Grammar {
+        Grammar(self.0 & !mask(param, num_params))
+    }
+
+    /// This grammar with `param` flipped.
+    pub fn toggle(&self, param: usize, num_params: usize) -> Grammar {
+        Grammar(self.0 ^ mask(param, num_params))
+    }
+
+    /// This grammar with `param` set to `value`.
+    pub fn with(&self, param: usize, value: bool, num_params: usize) -> Grammar {
+        if value {
+            self.set(param, num_params)
+        } else {
+            self.clear(param, num_params)
+        }
+    }
+
+    /// The number of parameters set differently in `other`.
+    pub fn hamming(&self, other: &Grammar) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+
+    /// The parameters set differently in `other`, in order.
+    pub fn differences(&self, other: &Grammar, num_params: usize) -> Vec<usize> {
+        (0..num_params)
+            .filter(|&param| self.get(param, num_params) != other.get(param, num_params))
+            .collect()
+    }
+
+    /// The grammars one parameter flip away, first parameter first. Some may
+    /// not be legal in a domain; see `LanguageDomain::legal_neighbours`.
+    pub fn neighbours(&self, num_params: usize) -> Vec<Grammar> {
+        (0..num_params).map(|param| self.toggle(param, num_params)).collect()
+    }
+
+    /// True if no bits beyond the first `num_params` are set.
+    pub fn fits(&self, num_params: usize) -> bool {
+        num_params <= MAX_PARAMS && (self.0 as u64) >> num_params == 0
+    }
+
+    /// One bit a parameter, first parameter first, like `0001001100011` for
+    /// COLAG English.
+    pub fn bit_string(&self, num_params: usize) -> String {
+        format!("{:0width$b}", self.0, width = num_params)
+    }
+}
+
+impl fmt::Display for Grammar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl FromStr for Grammar {
+    type Err = ParseIntError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Grammar)
+    }
+}
+
+/// Parses a grammar written as a string of `num_params` bits, as written by
+/// `bit_string`.
+pub fn parse_bits(s: &str, num_params: usize) -> Result<Grammar, String> {
+    if s.len() != num_params || !s.chars().all(|c| c == '0' || c == '1') {
+        return Err(format!("expected {} bits, saw {}", num_params, s));
+    }
+    u32::from_str_radix(s, 2).map(Grammar).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use grammar;
+    use grammar::Grammar;
+
+    const ENGLISH: Grammar = Grammar(611);
+
+    #[test]
+    fn setting_twice_leaves_a_grammar_alone() {
+        // parameter 0 is the most significant bit
+        assert_eq!(Grammar(0).set(0, 13), Grammar(1 << 12));
+        assert_eq!(Grammar(0).set(12, 13), Grammar(1));
+        let g = ENGLISH.set(3, 13);
+        assert_eq!(g, ENGLISH);
+        assert_eq!(g.set(3, 13), ENGLISH);
+        assert_eq!(ENGLISH.set(0, 13).set(0, 13), Grammar(611 | 1 << 12));
+        assert_eq!(ENGLISH.clear(3, 13).clear(3, 13), Grammar(611 & !(1 << 9)));
+        assert_eq!(ENGLISH.with(3, false, 13), ENGLISH.clear(3, 13));
+        assert_eq!(ENGLISH.with(0, true, 13), ENGLISH.set(0, 13));
+        assert!(ENGLISH.get(3, 13) && !ENGLISH.get(0, 13));
+    }
+
+    #[test]
+    fn toggling_flips_one_parameter() {
+        for param in 0..13 {
+            let g = ENGLISH.toggle(param, 13);
+            assert_eq!(g.get(param, 13), !ENGLISH.get(param, 13));
+            assert_eq!(g.hamming(&ENGLISH), 1);
+            assert_eq!(g.differences(&ENGLISH, 13), vec![param]);
+            assert_eq!(g.toggle(param, 13), ENGLISH);
+        }
+        assert_eq!(Grammar(5).neighbours(3), vec![Grammar(1), Grammar(7), Grammar(4)]);
+    }
+
+    #[test]
+    fn differences_are_in_parameter_order() {
+        assert_eq!(Grammar(0b101).differences(&Grammar(0b010), 3), vec![0, 1, 2]);
+        assert_eq!(Grammar(0b100).differences(&Grammar(0b001), 3), vec![0, 2]);
+        assert!(ENGLISH.differences(&ENGLISH, 13).is_empty());
+        assert_eq!(Grammar(0b1100).hamming(&Grammar(0b0110)), 2);
+    }
+
+    #[test]
+    fn grammars_fit_their_parameters() {
+        assert!(Grammar(7).fits(3) && !Grammar(8).fits(3));
+        assert!(Grammar(0).fits(0) && !Grammar(1).fits(0));
+        assert!(Grammar(u32::max_value()).fits(32) && !Grammar(0).fits(33));
+    }
+
+    #[test]
+    fn grammars_read_and_write_as_numbers_and_bits() {
+        assert_eq!(ENGLISH.to_string(), "611");
+        assert_eq!(format!("{:>5}", ENGLISH), "  611");
+        assert_eq!("611".parse(), Ok(ENGLISH));
+        assert!("-1".parse::<Grammar>().is_err());
+        assert_eq!(ENGLISH.bit_string(13), "0001001100011");
+        assert_eq!(grammar::parse_bits("0001001100011", 13), Ok(ENGLISH));
+        assert_eq!(grammar::parse_bits("000", 3), Ok(Grammar(0)));
+        assert_eq!(grammar::parse_bits("111", 3), Ok(Grammar(7)));
+        for g in (0..8).map(Grammar) {
+            assert_eq!(grammar::parse_bits(&g.bit_string(3), 3), Ok(g));
+        }
+        assert!(grammar::parse_bits("0101", 3).is_err());
+        assert!(grammar::parse_bits("012", 3).is_err());
+        assert!(grammar::parse_bits("+11", 3).is_err());
+        assert!(grammar::parse_bits("", 0).is_err());
+    }
+}
@@ -1,9 +1,18 @@
-use std::mem;
 use std::fmt;
+use std::str::FromStr;
+use std::collections::HashMap;
 
-use domain::{Grammar, NUM_PARAMS};
+use rand::Rng;
 
-type ParameterWeights = [f64; NUM_PARAMS];
+use domain::{Colag, Grammar};
+use grammar;
+use param;
+
+/// How close to 0 or 1 a parameter's weight has to be for a learner to count
+/// as having set it.
+pub const THRESHOLD: f64 = 0.02;
+
+type ParameterWeights = Vec<f64>;
 type FuzzyGrammar = ParameterWeights;
 
 // TODO: maybe add a second Grammar field. When we ask the learner for their
@@ -11,22 +20,110 @@ type FuzzyGrammar = ParameterWeights;
 // specific hypothesis.
 pub enum Theory<'a> {
     Simple(&'a SimpleHypothesis),
-    Weighted(&'a WeightedHypothesis)
+    Weighted(&'a WeightedHypothesis),
+    // no learner holds these kinds yet; one that does reports through them
+    #[allow(dead_code)]
+    Distribution(&'a DistributionHypothesis),
+    #[allow(dead_code)]
+    Partial(&'a PartialHypothesis)
+}
+
+// What a learner believes about the grammar of its language, however it
+// represents that.
+pub trait Hypothesis: fmt::Display {
+    fn num_params(&self) -> usize;
+    /// A grammar drawn from the hypothesis. It may not be legal in a domain.
+    fn sample(&self, rng: &mut dyn Rng) -> Grammar;
+    fn most_likely(&self) -> Grammar;
+    /// The probability the hypothesis gives `grammar`.
+    fn probability(&self, grammar: &Grammar) -> f64;
+    /// The probability of each parameter being set, in order.
+    fn beliefs(&self) -> Vec<f64>;
+    /// Writes the hypothesis on one line, starting with its kind, so that
+    /// `parse` can read it back exactly.
+    fn serialize(&self) -> String;
+
+    /// The Euclidean distance from the beliefs to the settings of `grammar`,
+    /// 0 when the hypothesis is certain of it.
+    fn distance(&self, grammar: &Grammar) -> f64 {
+        let num_params = self.num_params();
+        self.beliefs().iter()
+            .enumerate()
+            .map(|(param, b)| (b - grammar.get(param, num_params) as u8 as f64).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    }
+}
+
+/// Reads a hypothesis written by `Hypothesis::serialize`.
+pub fn parse(s: &str) -> Result<Box<dyn Hypothesis>, String> {
+    Ok(match s.split_whitespace().next() {
+        Some("simple") => Box::new(s.parse::<SimpleHypothesis>()?),
+        Some("weighted") => Box::new(s.parse::<WeightedHypothesis>()?),
+        Some("distribution") => Box::new(s.parse::<DistributionHypothesis>()?),
+        Some("partial") => Box::new(s.parse::<PartialHypothesis>()?),
+        _ => return Err(format!("Unknown hypothesis: {}", s))
+    })
 }
 
-pub trait Hypothesis {}
+// The fields of a serialized hypothesis of `kind`, after the kind.
+fn fields<'a>(s: &'a str, kind: &str) -> Result<Vec<&'a str>, String> {
+    let mut fields = s.split_whitespace();
+    if fields.next() != Some(kind) {
+        return Err(format!("Expected a {} hypothesis: {}", kind, s));
+    }
+    Ok(fields.collect())
+}
 
-#[derive(Debug)]
-pub struct SimpleHypothesis { pub grammar: Grammar }
+#[derive(Debug, Clone)]
+pub struct SimpleHypothesis { pub grammar: Grammar, pub num_params: usize }
 
 impl fmt::Display for SimpleHypothesis {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // write!(f, "SimpleHypothesis {{ grammar: {:013b} }}", self.grammar as i32)
-        write!(f, "{}", self.grammar as i32)
+        // write!(f, "SimpleHypothesis {{ grammar: {:013b} }}", self.grammar.0)
+        write!(f, "{}", self.grammar)
     }
 }
 
-#[derive(Debug)]
+impl Hypothesis for SimpleHypothesis {
+    fn num_params(&self) -> usize {
+        self.num_params
+    }
+
+    fn sample(&self, _rng: &mut dyn Rng) -> Grammar {
+        self.grammar
+    }
+
+    fn most_likely(&self) -> Grammar {
+        self.grammar
+    }
+
+    fn probability(&self, grammar: &Grammar) -> f64 {
+        (*grammar == self.grammar) as u8 as f64
+    }
+
+    fn beliefs(&self) -> Vec<f64> {
+        WeightedHypothesis::from_grammar(&self.grammar, self.num_params).weights
+    }
+
+    /// `simple BITS`
+    fn serialize(&self) -> String {
+        format!("simple {}", self.grammar.bit_string(self.num_params))
+    }
+}
+
+impl FromStr for SimpleHypothesis {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match fields(s, "simple")?.as_slice() {
+            &[bits] => Ok(SimpleHypothesis { grammar: grammar::parse_bits(bits, bits.len())?,
+                                             num_params: bits.len() }),
+            _ => Err(format!("Expected one grammar: {}", s))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct WeightedHypothesis { pub weights: ParameterWeights }
 
 impl fmt::Display for WeightedHypothesis {
@@ -35,7 +132,12 @@ impl fmt::Display for WeightedHypothesis {
         //        Colag::random_weighted_grammar(self.weights));
         // write!(f, "WeightedHypothesis {{ weights: [")?;
         // write!(f, "{}, ", Colag::random_weighted_grammar(self.weights))?;
-        for i in 0..NUM_PARAMS {
+        // `{:#}` labels each weight with its parameter, like `SP=0.500, `
+        let num_params = self.weights.len();
+        for i in 0..num_params {
+            if f.alternate() {
+                write!(f, "{}=", param::label(i, num_params))?;
+            }
             write!(f, "{:.3}, ", self.weights[i])?;
         }
         // write!(f, "]}}")?;
@@ -43,16 +145,412 @@ impl fmt::Display for WeightedHypothesis {
     }
 }
 
-impl Hypothesis for WeightedHypothesis {}
+impl Hypothesis for WeightedHypothesis {
+    fn num_params(&self) -> usize {
+        self.weights.len()
+    }
+
+    fn sample(&self, mut rng: &mut dyn Rng) -> Grammar {
+        Colag::random_weighted_grammar(&mut rng, &self.weights)
+    }
+
+    /// The grammar with every parameter set to its more likely value.
+    fn most_likely(&self) -> Grammar {
+        let num_params = self.weights.len();
+        let mut grammar = Grammar(0);
+        for param in 0..num_params {
+            if self.weights[param] > 0.5 {
+                grammar = grammar.set(param, num_params);
+            }
+        }
+        grammar
+    }
+
+    fn probability(&self, grammar: &Grammar) -> f64 {
+        let num_params = self.weights.len();
+        self.weights.iter()
+            .enumerate()
+            .map(|(param, w)| if grammar.get(param, num_params) { *w } else { 1. - w })
+            .product()
+    }
+
+    fn beliefs(&self) -> Vec<f64> {
+        self.weights.clone()
+    }
+
+    /// `weighted WEIGHT...`, written exactly
+    fn serialize(&self) -> String {
+        let weights: Vec<String> = self.weights.iter().map(|w| w.to_string()).collect();
+        format!("weighted {}", weights.join(" "))
+    }
+}
+
+impl FromStr for WeightedHypothesis {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let weights = fields(s, "weighted")?.iter()
+            .map(|w| match w.parse::<f64>() {
+                Ok(weight) if weight >= 0. && weight <= 1. => Ok(weight),
+                _ => Err(format!("Bad weight {}: expected a number from 0 to 1", w))
+            })
+            .collect::<Result<ParameterWeights, _>>()?;
+        Ok(WeightedHypothesis { weights })
+    }
+}
 
 impl WeightedHypothesis {
-    pub fn new() -> Self {
-        unsafe {
-            let mut array: ParameterWeights = mem::uninitialized();
-            for param in 0..NUM_PARAMS {
-                array[param] = 0.5;
+    pub fn new(num_params: usize) -> Self {
+        WeightedHypothesis { weights: vec![0.5; num_params] }
+    }
+
+    /// Returns the hypothesis that is certain of `grammar`.
+    pub fn from_grammar(grammar: &Grammar, num_params: usize) -> Self {
+        let mut hypothesis = WeightedHypothesis::new(num_params);
+        for param in 0..num_params {
+            hypothesis.weights[param] = grammar.get(param, num_params) as u8 as f64;
+        }
+        hypothesis
+    }
+}
+
+// A probability distribution over whole grammars. Grammars not listed have
+// probability 0.
+#[derive(Debug, Clone)]
+pub struct DistributionHypothesis {
+    num_params: usize,
+    // most likely first
+    grammars: Vec<(Grammar, f64)>
+}
+
+impl DistributionHypothesis {
+    /// A distribution in proportion to the weight of each grammar, with the
+    /// weights of a grammar listed twice added together. The weights must be
+    /// finite and not negative, some must be positive, and the grammars must
+    /// fit in `num_params` parameters. Weights that already add up to 1 are
+    /// kept as they are, so that a serialized distribution reads back exactly.
+    pub fn new(num_params: usize, weights: Vec<(Grammar, f64)>) -> Result<Self, String> {
+        if num_params > grammar::MAX_PARAMS {
+            return Err(format!("Grammars can't have {} parameters", num_params));
+        }
+        let mut combined: HashMap<Grammar, f64> = HashMap::new();
+        for (g, w) in weights {
+            if !w.is_finite() || w < 0. {
+                return Err(format!("Bad weight {} for grammar {}", w, g));
+            }
+            if !g.fits(num_params) {
+                return Err(format!("Grammar {} doesn't fit in {} parameters", g, num_params));
+            }
+            *combined.entry(g).or_insert(0.) += w;
+        }
+        let total: f64 = combined.values().sum();
+        if total <= 0. {
+            return Err("No grammar has any weight".to_string());
+        }
+        let scale = if (total - 1.).abs() < 1e-9 { 1. } else { total };
+        let mut grammars: Vec<(Grammar, f64)> = combined.into_iter()
+            .filter(|&(_, w)| w > 0.)
+            .map(|(g, w)| (g, w / scale))
+            .collect();
+        grammars.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+        Ok(DistributionHypothesis { num_params, grammars })
+    }
+
+    /// Each grammar and its probability, most likely first.
+    pub fn grammars(&self) -> &Vec<(Grammar, f64)> {
+        &self.grammars
+    }
+}
+
+// `grammar=probability, ` for each grammar, most likely first
+impl fmt::Display for DistributionHypothesis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &(grammar, p) in self.grammars.iter() {
+            write!(f, "{}={:.4}, ", grammar, p)?;
+        }
+        Ok(())
+    }
+}
+
+impl Hypothesis for DistributionHypothesis {
+    fn num_params(&self) -> usize {
+        self.num_params
+    }
+
+    fn sample(&self, rng: &mut dyn Rng) -> Grammar {
+        let mut x = rng.next_f64();
+        for &(grammar, p) in self.grammars.iter() {
+            if x < p {
+                return grammar;
             }
-            WeightedHypothesis { weights: array }
+            x -= p;
         }
+        // rounding left a sliver at the end
+        self.grammars.last().expect("empty distribution").0
+    }
+
+    fn most_likely(&self) -> Grammar {
+        self.grammars.first().expect("empty distribution").0
+    }
+
+    fn probability(&self, grammar: &Grammar) -> f64 {
+        self.grammars.iter()
+            .find(|&&(g, _)| g == *grammar)
+            .map_or(0., |&(_, p)| p)
+    }
+
+    fn beliefs(&self) -> Vec<f64> {
+        let mut beliefs = vec![0.; self.num_params];
+        for &(grammar, p) in self.grammars.iter() {
+            for param in 0..self.num_params {
+                if grammar.get(param, self.num_params) {
+                    beliefs[param] += p;
+                }
+            }
+        }
+        beliefs
+    }
+
+    /// `distribution NUM_PARAMS GRAMMAR=PROBABILITY...`, written exactly
+    fn serialize(&self) -> String {
+        let grammars: Vec<String> = self.grammars.iter()
+            .map(|&(g, p)| format!("{}={}", g, p))
+            .collect();
+        format!("distribution {} {}", self.num_params, grammars.join(" "))
+    }
+}
+
+impl FromStr for DistributionHypothesis {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = fields(s, "distribution")?;
+        let num_params = fields.first()
+            .ok_or_else(|| format!("Expected a number of parameters: {}", s))?
+            .parse::<usize>().map_err(|e| e.to_string())?;
+        let mut grammars = Vec::new();
+        for field in fields[1..].iter() {
+            let mut parts = field.splitn(2, '=');
+            match (parts.next().map(str::parse::<Grammar>), parts.next().map(str::parse::<f64>)) {
+                (Some(Ok(g)), Some(Ok(p))) if p > 0. && p <= 1. => grammars.push((g, p)),
+                _ => return Err(format!("Expected GRAMMAR=PROBABILITY, saw {}", field))
+            }
+        }
+        DistributionHypothesis::new(num_params, grammars)
+            .map_err(|e| format!("{}: {}", e, s))
+    }
+}
+
+// Parameters that are either settled or still open. Every grammar that
+// agrees with the settled ones is equally likely.
+#[derive(Debug, Clone)]
+pub struct PartialHypothesis { pub settings: Vec<Option<bool>> }
+
+impl PartialHypothesis {
+    /// A hypothesis with nothing settled yet.
+    pub fn new(num_params: usize) -> Self {
+        PartialHypothesis { settings: vec![None; num_params] }
+    }
+
+    /// The parameters still open, in order.
+    pub fn unset(&self) -> Vec<usize> {
+        (0..self.settings.len()).filter(|&p| self.settings[p].is_none()).collect()
+    }
+}
+
+// one character a parameter: `0`, `1`, or `*` if it's open
+impl fmt::Display for PartialHypothesis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for setting in self.settings.iter() {
+            write!(f, "{}", match *setting {
+                Some(false) => '0',
+                Some(true) => '1',
+                None => '*'
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl Hypothesis for PartialHypothesis {
+    fn num_params(&self) -> usize {
+        self.settings.len()
+    }
+
+    fn sample(&self, rng: &mut dyn Rng) -> Grammar {
+        let num_params = self.settings.len();
+        self.settings.iter()
+            .enumerate()
+            .fold(Grammar(0), |grammar, (param, setting)| {
+                let value = setting.unwrap_or_else(|| rng.next_f64() < 0.5);
+                grammar.with(param, value, num_params)
+            })
+    }
+
+    /// The grammar with the settled parameters, and open ones off.
+    fn most_likely(&self) -> Grammar {
+        let num_params = self.settings.len();
+        self.settings.iter()
+            .enumerate()
+            .fold(Grammar(0), |grammar, (param, setting)| {
+                grammar.with(param, setting.unwrap_or(false), num_params)
+            })
+    }
+
+    fn probability(&self, grammar: &Grammar) -> f64 {
+        let num_params = self.settings.len();
+        let agrees = self.settings.iter()
+            .enumerate()
+            .all(|(param, setting)| setting.map_or(true, |v| grammar.get(param, num_params) == v));
+        if agrees {
+            0.5f64.powi(self.unset().len() as i32)
+        } else {
+            0.
+        }
+    }
+
+    fn beliefs(&self) -> Vec<f64> {
+        self.settings.iter()
+            .map(|setting| setting.map_or(0.5, |v| v as u8 as f64))
+            .collect()
+    }
+
+    /// `partial SETTINGS`, written as by Display
+    fn serialize(&self) -> String {
+        format!("partial {}", self)
+    }
+}
+
+impl FromStr for PartialHypothesis {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let settings = match fields(s, "partial")?.as_slice() {
+            &[settings] => settings,
+            _ => return Err(format!("Expected one string of settings: {}", s))
+        };
+        let settings = settings.chars()
+            .map(|c| match c {
+                '0' => Ok(Some(false)),
+                '1' => Ok(Some(true)),
+                '*' => Ok(None),
+                _ => Err(format!("Bad setting {} in {}", c, s))
+            })
+            .collect::<Result<Vec<Option<bool>>, _>>()?;
+        Ok(PartialHypothesis { settings })
+    }
+}
+
+impl<'a> Theory<'a> {
+    /// The hypothesis behind the theory, whatever its kind.
+    pub fn hypothesis(&self) -> &'a dyn Hypothesis {
+        match *self {
+            Theory::Simple(h) => h,
+            Theory::Weighted(h) => h,
+            Theory::Distribution(h) => h,
+            Theory::Partial(h) => h
+        }
+    }
+
+    /// Returns the theory as parameter weights, the probability of each
+    /// parameter being set.
+    pub fn weighted(&self) -> WeightedHypothesis {
+        WeightedHypothesis { weights: self.beliefs() }
+    }
+}
+
+impl<'a> fmt::Display for Theory<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.hypothesis().fmt(f)
+    }
+}
+
+impl<'a> Hypothesis for Theory<'a> {
+    fn num_params(&self) -> usize {
+        self.hypothesis().num_params()
+    }
+
+    fn sample(&self, rng: &mut dyn Rng) -> Grammar {
+        self.hypothesis().sample(rng)
+    }
+
+    fn most_likely(&self) -> Grammar {
+        self.hypothesis().most_likely()
+    }
+
+    fn probability(&self, grammar: &Grammar) -> f64 {
+        self.hypothesis().probability(grammar)
+    }
+
+    fn beliefs(&self) -> Vec<f64> {
+        self.hypothesis().beliefs()
+    }
+
+    fn serialize(&self) -> String {
+        self.hypothesis().serialize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hypothesis;
+    use hypothesis::{Hypothesis, SimpleHypothesis, WeightedHypothesis, DistributionHypothesis,
+                     PartialHypothesis};
+    use domain::Grammar;
+
+    fn weights(pairs: &[(u32, f64)]) -> Vec<(Grammar, f64)> {
+        pairs.iter().map(|&(g, w)| (Grammar(g), w)).collect()
+    }
+
+    // Reads back what `hypothesis` wrote and checks it's the same.
+    fn round_trip(hypothesis: &dyn Hypothesis) {
+        let line = hypothesis.serialize();
+        let read = hypothesis::parse(&line).unwrap();
+        assert_eq!(read.serialize(), line);
+        assert_eq!(read.num_params(), hypothesis.num_params());
+        assert_eq!(read.beliefs(), hypothesis.beliefs());
+        assert_eq!(read.most_likely(), hypothesis.most_likely());
+        assert_eq!(read.to_string(), hypothesis.to_string());
+    }
+
+    #[test]
+    fn every_kind_reads_back_exactly() {
+        round_trip(&SimpleHypothesis { grammar: Grammar(611), num_params: 13 });
+        round_trip(&SimpleHypothesis { grammar: Grammar(0), num_params: 3 });
+        round_trip(&WeightedHypothesis { weights: vec![0.1, 1. / 3., 0.999999999999, 0.] });
+        round_trip(&WeightedHypothesis::new(13));
+        round_trip(&DistributionHypothesis::new(3, weights(&[(4, 2.), (5, 1.), (7, 7.)])).unwrap());
+        round_trip(&DistributionHypothesis::new(13, weights(&[(611, 1.)])).unwrap());
+        round_trip(&PartialHypothesis { settings: vec![Some(true), None, Some(false)] });
+        round_trip(&PartialHypothesis::new(13));
+    }
+
+    #[test]
+    fn bad_lines_are_rejected() {
+        for line in ["", "simple", "simple 012", "weighted 0.5 x", "distribution", "distribution 3",
+                     "distribution 3 4", "distribution 3 4=0", "distribution 3 4=-1",
+                     "distribution 3 8=1", "distribution 33 1=1", "weighted 0.5 1.5",
+                     "weighted -0.1", "weighted NaN", "weighted inf",
+                     "partial 01?", "partial 01 10", "unknown 1"].iter() {
+            assert!(hypothesis::parse(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn distributions_need_some_weight() {
+        assert!(DistributionHypothesis::new(3, weights(&[])).is_err());
+        assert!(DistributionHypothesis::new(3, weights(&[(4, 0.), (5, 0.)])).is_err());
+        assert!(DistributionHypothesis::new(3, weights(&[(4, 1.), (5, -1.)])).is_err());
+        assert!(DistributionHypothesis::new(3, weights(&[(4, ::std::f64::NAN)])).is_err());
+        let d = DistributionHypothesis::new(3, weights(&[(4, 1.), (5, 3.), (6, 0.)])).unwrap();
+        assert_eq!(d.grammars(), &vec![(Grammar(5), 0.75), (Grammar(4), 0.25)]);
+        assert_eq!(d.most_likely(), Grammar(5));
+        assert!(DistributionHypothesis::new(3, weights(&[(8, 1.)])).is_err());
+    }
+
+    #[test]
+    fn read_distributions_are_normalised_sorted_and_combined() {
+        let read: DistributionHypothesis = "distribution 3 4=0.25 5=0.5 4=0.25".parse().unwrap();
+        assert_eq!(read.grammars(), &vec![(Grammar(4), 0.5), (Grammar(5), 0.5)]);
+        let read: DistributionHypothesis = "distribution 3 6=0.125 7=0.375".parse().unwrap();
+        assert_eq!(read.grammars(), &vec![(Grammar(7), 0.75), (Grammar(6), 0.25)]);
+        assert_eq!(read.most_likely(), Grammar(7));
     }
 }
@@ -1,13 +1,14 @@
 use std::fmt::Display;
 
 use hypothesis::{Theory};
+use checkpoint::Checkpoint;
 use domain::{Sentence, Colag, Grammar};
 
-// pub mod trigger;
+pub mod trigger;
 pub mod variational;
 // pub mod ndl;
 
-// pub use self::trigger::TriggerLearner;
+pub use self::trigger::TriggerLearner;
 pub use self::variational::{RewardOnlyVL, RewardOnlyRelevantVL};
 // pub use self::ndl::NonDefaultsLearner;
 
@@ -18,8 +19,10 @@ pub struct Environment {
 // Represents a language learner. They learn from sentences as their input, and
 // update their internal hypothesis about which grammar generated the language
 // they're seeing. After they have tested/updated this hypothesis for a while
-// they will have a Theory as to which grammar generated their language.
-pub trait Learner: Display {
+// they will have a Theory as to which grammar generated their language. A
+// learner's checkpoint holds everything it has learned, including the state
+// of its rng.
+pub trait Learner: Display + Checkpoint {
     fn learn(&mut self, &Environment, &Sentence);
     fn converged(&mut self) -> bool {
         false
@@ -1,25 +1,9 @@
 use learner::{Learner, Environment};
 use hypothesis::{WeightedHypothesis, Theory};
 use sentence::{SurfaceForm, Illoc};
-use domain::{LanguageDomain, Sentence};
-
-
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
-enum Param {
-    SP,
-    HIP,
-    HCP,
-    OPT,
-    NS,
-    NT,
-    WHM,
-    PI,
-    TM,
-    VtoI,
-    ItoC,
-    AH,
-    QInv
-}
+use domain::{LanguageDomain, Sentence, COLAG_PARAMS};
+use param::Param;
+
 
 #[derive(Debug)]
 enum Rate {
@@ -39,7 +23,8 @@ pub struct NonDefaultsLearner {
 
 impl Learner for NonDefaultsLearner {
     fn learn(&mut self, env: &Environment, sent: &Sentence){
-        let surface_form = env.domain.surface_form(sent);
+        let surface_form = env.domain.surface_form(sent)
+            .unwrap_or_else(|e| panic!("{}", e));
         let ops = self.run_triggers(surface_form);
         // let mut params: HashSet<Param> = HashSet::new();
         for op in ops {
@@ -67,9 +52,9 @@ impl Learner for NonDefaultsLearner {
 
 impl NonDefaultsLearner {
     pub fn new() -> Self {
-        NonDefaultsLearner { hypothesis: WeightedHypothesis::new() }
+        NonDefaultsLearner { hypothesis: WeightedHypothesis::new(COLAG_PARAMS) }
     }
-    pub fn boxed() -> Box<Learner> {
+    pub fn boxed(_env: &Environment) -> Box<Learner> {
         Box::new(Self::new())
     }
 
@@ -216,18 +201,22 @@ impl NonDefaultsLearner {
     }
 }
 
+#[cfg(test)]
 mod bench {
     extern crate test;
     use self::test::Bencher;
     use learner::{NonDefaultsLearner, Learner, Environment};
-    use domain::{Colag};
+    use domain::{Colag, Grammar};
     use speaker::{UniformRandomSpeaker};
 
     #[bench]
     fn non_defaults_learner(b: &mut Bencher) {
-        let colag = Colag::default();
+        let colag = match Colag::for_bench() {
+            Some(colag) => colag,
+            None => return
+        };
         let env = Environment { domain: colag };
-        let mut speaker = UniformRandomSpeaker::new(&env.domain, 611);
+        let mut speaker = UniformRandomSpeaker::new(&env.domain, Grammar(611)).unwrap();
         let mut learner = NonDefaultsLearner::new();
         b.iter(|| learner.learn(&env, speaker.next().unwrap()));
     }
@@ -1,77 +1,163 @@
+use std::fmt;
+use rand::Rng;
 use learner::{Learner, Environment};
-use domain::{Sentence, IllegalGrammar, LanguageDomain};
+use domain::{Sentence, Grammar, LanguageDomain};
+use grammar;
 use hypothesis::{SimpleHypothesis, Theory};
+use checkpoint;
+use checkpoint::{Checkpoint, ResumableRng};
 
-use rand;
-
+// The Triggering Learning Algorithm (Gibson & Wexler 1994), the learner that
+// tla::TlaChain solves exactly. When its grammar fails to parse a sentence it
+// flips one parameter chosen at random, and keeps the flip only if the new
+// grammar parses the sentence (single value constraint + greediness).
 pub struct TriggerLearner {
     hypothesis: SimpleHypothesis,
     clean_parses: u32,
-    rng: rand::XorShiftRng,
+    rng: ResumableRng,
 }
 
 impl Learner for TriggerLearner {
     fn learn(&mut self, env: &Environment, sent: &Sentence){
-        let parses = env.domain.parses(&self.hypothesis.grammar, sent);
-        match parses {
-            Ok(false) | Err(IllegalGrammar {..}) => {
-                // TODO: should this only be guessing legal grammars?
-                let new_grammar = *env.domain.random_grammar(&mut self.rng);
-                if let Ok(true) = env.domain.parses(&new_grammar, sent){
-                    // if the new grammar is a better hypothesis, adopt it.
-                    self.hypothesis.grammar = new_grammar;
+        let num_params = self.hypothesis.num_params;
+        match env.domain.parses(&self.hypothesis.grammar, sent) {
+            Ok(true) => {
+                self.clean_parses += 1;
+                // our hypothesis worked, let's keep it.
+            },
+            Ok(false) => {
+                let param = self.rng.gen_range(0, num_params);
+                let flipped = self.hypothesis.grammar.toggle(param, num_params);
+                // an illegal neighbour parses nothing, so we stay put.
+                if let Ok(true) = env.domain.parses(&flipped, sent) {
+                    self.hypothesis.grammar = flipped;
                 }
                 self.clean_parses = 0;
-                // the new grammar also failed to parse the input. let's follow
-                // the greediness princple and not change our minds.
             },
-            _ => {
-                self.clean_parses += 1;
-                // our hypothesis worked, let's keep it.
+            Err(_) => {
+                // we started outside the domain, so there's nothing to flip
+                // from yet.
+                self.hypothesis.grammar = *env.domain.random_grammar(&mut self.rng);
+                self.clean_parses = 0;
             }
         }
     }
     fn theory<'a>(&'a self) -> Theory<'a> {
         Theory::Simple(&self.hypothesis)
     }
+    fn guess(&mut self) -> Grammar {
+        self.hypothesis.grammar
+    }
     fn converged(&mut self) -> bool {
         self.clean_parses > 1000
     }
 }
 
+impl fmt::Display for TriggerLearner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TriggerLearner")
+    }
+}
+
+// grammar; clean parses; rng
+impl Checkpoint for TriggerLearner {
+    fn save(&self) -> String {
+        let num_params = self.hypothesis.num_params;
+        checkpoint::join(&[self.hypothesis.grammar.bit_string(num_params),
+                           self.clean_parses.to_string(), self.rng.save()])
+    }
+    fn restore(&mut self, state: &str) -> Result<(), String> {
+        let fields = checkpoint::split(state, 3)?;
+        self.hypothesis.grammar = grammar::parse_bits(fields[0], self.hypothesis.num_params)?;
+        self.clean_parses = fields[1].parse().map_err(|_| format!("Bad count: {}", fields[1]))?;
+        self.rng = ResumableRng::restore(fields[2])?;
+        Ok(())
+    }
+}
+
 impl TriggerLearner {
-    pub fn new() -> Self {
-        TriggerLearner { hypothesis: SimpleHypothesis {grammar: 0},
+    pub fn new(num_params: usize) -> Self {
+        TriggerLearner::starting_at(Grammar(0), num_params)
+    }
+    /// A learner whose first guess is `grammar`.
+    pub fn starting_at(grammar: Grammar, num_params: usize) -> Self {
+        TriggerLearner { hypothesis: SimpleHypothesis { grammar, num_params },
                          clean_parses: 0,
-                         rng: rand::weak_rng()}
+                         rng: checkpoint::new_rng() }
     }
-    pub fn boxed() -> Box<Learner> {
-        Box::new(TriggerLearner::new())
+    /// A learner starting from a random grammar of the domain.
+    pub fn boxed(env: &Environment) -> Box<dyn Learner> {
+        let mut learner = TriggerLearner::new(env.domain.num_params());
+        learner.hypothesis.grammar = *env.domain.random_grammar(&mut learner.rng);
+        Box::new(learner)
     }
 }
 
+#[cfg(test)]
 mod bench {
     extern crate test;
     use self::test::Bencher;
     use learner::{TriggerLearner, Learner, Environment};
-    use domain::{Colag};
+    use domain::{Colag, LanguageDomain, Grammar};
     use speaker::{UniformRandomSpeaker};
 
     #[bench]
     fn trigger_learner_speaker(b: &mut Bencher) {
-        let colag = Colag::default();
+        let colag = match Colag::for_bench() {
+            Some(colag) => colag,
+            None => return
+        };
         let env = Environment { domain: colag };
-        let mut speaker = UniformRandomSpeaker::new(&env.domain, 611);
-        let mut learner = TriggerLearner::new();
+        let mut speaker = UniformRandomSpeaker::new(&env.domain, Grammar(611)).unwrap();
+        let mut learner = TriggerLearner::new(env.domain.num_params());
         b.iter(|| learner.learn(&env, speaker.next().unwrap()));
     }
 
     #[bench]
     fn trigger_learner_constant(b: &mut Bencher) {
-        let colag = Colag::default();
+        let colag = match Colag::for_bench() {
+            Some(colag) => colag,
+            None => return
+        };
         let env = Environment { domain: colag };
         let ref sent = 400;
-        let mut learner = TriggerLearner::new();
+        let mut learner = TriggerLearner::new(env.domain.num_params());
         b.iter(|| learner.learn(&env, sent));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use checkpoint;
+    use checkpoint::Checkpoint;
+    use domain::{LanguageDomain, Grammar};
+    use learner::{TriggerLearner, Learner, Environment};
+    use speaker::UniformRandomSpeaker;
+    use toy;
+
+    #[test]
+    fn trigger_learner_settles_on_a_grammar_that_parses_the_target() {
+        checkpoint::seed_thread(48);
+        let env = Environment { domain: toy::gibson_wexler() };
+        // the TLA reaches 4 from 7 for certain (see tla::TlaChain)
+        let mut learner = TriggerLearner::starting_at(Grammar(7), env.domain.num_params());
+        let speaker = UniformRandomSpeaker::new(&env.domain, Grammar(4)).unwrap();
+        for sent in speaker.take(5000) {
+            learner.learn(&env, sent);
+        }
+        assert!(learner.converged());
+        let guess = learner.guess();
+        assert!(env.domain.language(&Grammar(4)).unwrap().iter()
+                .all(|s| env.domain.parses(&guess, s).unwrap()));
+
+        let mut restored = TriggerLearner::new(env.domain.num_params());
+        restored.restore(&learner.save()).unwrap();
+        assert_eq!(restored.hypothesis.grammar, guess);
+        assert_eq!(restored.clean_parses, learner.clean_parses);
+        for _ in 0..1000 {
+            assert_eq!(restored.rng.next_u64(), learner.rng.next_u64());
+        }
+        assert!(restored.restore("0101; 0; 1 2 3").is_err());
+    }
+}
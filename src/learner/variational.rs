@@ -1,20 +1,15 @@
 use std::fmt;
+use std::sync::Arc;
 use learner::{Learner, Environment};
-use domain::{Grammar, Sentence, NUM_PARAMS, LanguageDomain, Colag, get_param, Trigger};
-use hypothesis::{WeightedHypothesis, Theory};
+use domain::{Grammar, Sentence, LanguageDomain, Colag, Trigger, DomainError};
+use triggers::TriggerMap;
+use hypothesis::{Hypothesis, WeightedHypothesis, Theory, THRESHOLD};
+use checkpoint;
+use checkpoint::{Checkpoint, ResumableRng};
 
-use triggers::{TriggerMap};
-
-use rand;
-use rand::{SeedableRng, Rng};
-
-use mersenne_twister::MersenneTwister;
-use std::default::Default;
-
-type RngType = MersenneTwister;
+type RngType = ResumableRng;
 
 const LEARNING_RATE: f64 = 0.001;
-const THRESHOLD: f64 = 0.02;
 
 // reward only VL
 
@@ -24,13 +19,12 @@ pub struct RewardOnlyVL {
 }
 
 impl RewardOnlyVL {
-    pub fn new() -> RewardOnlyVL {
-        let mut seed = rand::thread_rng();
-        RewardOnlyVL { hypothesis: WeightedHypothesis::new(),
-                       rng: MersenneTwister::from_seed(seed.next_u64()) }
+    pub fn new(num_params: usize) -> RewardOnlyVL {
+        RewardOnlyVL { hypothesis: WeightedHypothesis::new(num_params),
+                       rng: checkpoint::new_rng() }
     }
-    pub fn boxed() -> Box<Learner> {
-        Box::new(RewardOnlyVL::new())
+    pub fn boxed(env: &Environment) -> Box<dyn Learner> {
+        Box::new(RewardOnlyVL::new(env.domain.num_params()))
     }
 
     pub fn guess(&mut self) -> Grammar {
@@ -40,8 +34,9 @@ impl RewardOnlyVL {
     fn reward(&mut self, _: &Environment, gram: &Grammar, _: &Sentence){
         let ref mut hyp = self.hypothesis;
         let ref mut weights = hyp.weights;
-        for param in 0..NUM_PARAMS {
-            if get_param(gram, param) == 0 {
+        let num_params = weights.len();
+        for param in 0..num_params {
+            if !gram.get(param, num_params) {
                 weights[param] -= LEARNING_RATE * weights[param];
             } else {
                 weights[param] += LEARNING_RATE * (1. - weights[param]);
@@ -95,38 +90,76 @@ impl Learner for RewardOnlyVL {
     }
 }
 
-pub struct RewardOnlyRelevantVL<'a> {
-    name: String,
+pub struct RewardOnlyRelevantVL {
+    // the name of the domain's trigger table, for display
+    label: String,
+    // that trigger table, which has triggers for every sentence of the domain
+    triggers: Arc<TriggerMap>,
     hypothesis: WeightedHypothesis,
     irrelevant_learning_rate: f64,
-    trigger_map: &'a TriggerMap,
-    activated: [u32; NUM_PARAMS], // indicates if a weight has ever been adjusted
+    activated: Vec<u32>, // indicates if a weight has ever been adjusted
     consumed: u64,
     rng: RngType
 }
 
-impl<'a> fmt::Display for RewardOnlyRelevantVL<'a> {
+// weights; rng
+impl Checkpoint for RewardOnlyVL {
+    fn save(&self) -> String {
+        checkpoint::join(&[self.hypothesis.serialize(), self.rng.save()])
+    }
+    fn restore(&mut self, state: &str) -> Result<(), String> {
+        let fields = checkpoint::split(state, 2)?;
+        self.hypothesis = restore_weights(fields[0], self.hypothesis.weights.len())?;
+        self.rng = ResumableRng::restore(fields[1])?;
+        Ok(())
+    }
+}
+
+// Reads weights saved by `Hypothesis::serialize`, checking they're for
+// `num_params` parameters.
+fn restore_weights(state: &str, num_params: usize) -> Result<WeightedHypothesis, String> {
+    let hypothesis: WeightedHypothesis = state.parse()?;
+    if hypothesis.weights.len() != num_params {
+        return Err(format!("Expected {} weights, saw {}", num_params, hypothesis.weights.len()));
+    }
+    Ok(hypothesis)
+}
+
+impl fmt::Display for RewardOnlyRelevantVL {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "RewardOnlyRelevant[{}:{}]", self.name, self.irrelevant_learning_rate)
+        write!(f, "RewardOnlyRelevant[{}:{}]", self.label, self.irrelevant_learning_rate)
     }
 }
 
-impl<'a> RewardOnlyRelevantVL<'a> {
-    pub fn new(name: &str, trigger_map: &'a TriggerMap, irrel_rate: f64) -> RewardOnlyRelevantVL<'a> {
-        RewardOnlyRelevantVL { hypothesis: WeightedHypothesis::new(),
-                               trigger_map: trigger_map,
-                               irrelevant_learning_rate: irrel_rate,
-                               activated: [0; NUM_PARAMS],
-                               name: name.to_string(),
-                               consumed: 0,
-                               rng: Default::default() }
+impl RewardOnlyRelevantVL {
+    /// A learner of `domain` that moves on the triggers in its table called
+    /// `table`, which must cover every sentence of the domain.
+    pub fn new(domain: &Colag, table: &str,
+               irrel_rate: f64) -> Result<RewardOnlyRelevantVL, DomainError> {
+        let triggers = domain.shared_trigger_table(table)?.clone();
+        if let Some(s) = domain.all_sentences().iter().find(|s| triggers.sentence(s).is_none()) {
+            return Err(DomainError::NoTriggers(*s));
+        }
+        let num_params = domain.num_params();
+        Ok(RewardOnlyRelevantVL { hypothesis: WeightedHypothesis::new(num_params),
+                                  irrelevant_learning_rate: irrel_rate,
+                                  activated: vec![0; num_params],
+                                  label: table.to_string(),
+                                  triggers,
+                                  consumed: 0,
+                                  rng: checkpoint::new_rng() })
     }
 
-    fn reward(&mut self, env: &Environment, gram: &Grammar, sent: &Sentence){
-        let triggers = self.trigger_map.sentence(&sent)
-            .expect(&format!("no trigger found for {}", &sent));
+    fn reward(&mut self, _env: &Environment, gram: &Grammar, sent: &Sentence){
+        // only sentences of the domain parse, and `new` checked they all have
+        // triggers
+        let triggers = match self.triggers.sentence(sent) {
+            Some(triggers) => triggers,
+            None => return
+        };
         let ref mut weights = self.hypothesis.weights;
-        for param in 0..NUM_PARAMS {
+        let num_params = weights.len();
+        for param in 0..num_params {
             let rate = match triggers[param] {
                 Trigger::On | Trigger::Off => {
                     self.activated[param] += 1;
@@ -140,7 +173,7 @@ impl<'a> RewardOnlyRelevantVL<'a> {
                     LEARNING_RATE * self.irrelevant_learning_rate
                 },
             };
-            if get_param(gram, param) == 0 {
+            if !gram.get(param, num_params) {
                 weights[param] -= rate * weights[param]
             } else {
                 weights[param] += rate * (1. - weights[param])
@@ -152,7 +185,7 @@ impl<'a> RewardOnlyRelevantVL<'a> {
     }
 }
 
-impl<'a> Learner for RewardOnlyRelevantVL<'a> {
+impl Learner for RewardOnlyRelevantVL {
     fn learn(&mut self, env: &Environment, sent: &Sentence){
         loop {
             let g = Colag::random_weighted_grammar(&mut self.rng,
@@ -193,34 +226,143 @@ impl<'a> Learner for RewardOnlyRelevantVL<'a> {
     }
 }
 
+// weights; activations; sentences consumed; rng
+impl Checkpoint for RewardOnlyRelevantVL {
+    fn save(&self) -> String {
+        checkpoint::join(&[self.hypothesis.serialize(), checkpoint::save_list(&self.activated),
+                           self.consumed.to_string(), self.rng.save()])
+    }
+    fn restore(&mut self, state: &str) -> Result<(), String> {
+        let fields = checkpoint::split(state, 4)?;
+        let num_params = self.hypothesis.weights.len();
+        let activated: Vec<u32> = checkpoint::restore_list(fields[1])?;
+        if activated.len() != num_params {
+            return Err(format!("Expected {} activations, saw {}", num_params, activated.len()));
+        }
+        self.hypothesis = restore_weights(fields[0], num_params)?;
+        self.activated = activated;
+        self.consumed = fields[2].parse().map_err(|_| format!("Bad count: {}", fields[2]))?;
+        self.rng = ResumableRng::restore(fields[3])?;
+        Ok(())
+    }
+}
+
 
+#[cfg(test)]
 mod bench {
     extern crate test;
     use self::test::Bencher;
     use learner::{RewardOnlyVL, RewardOnlyRelevantVL, Learner, Environment};
-    use domain::{Colag, LanguageDomain, Sentence, Grammar};
+    use domain::{Colag, LanguageDomain, Grammar};
     use speaker::{UniformRandomSpeaker};
-    use triggers::{TriggerMap};
+    use toy;
 
     #[bench]
     fn reward_only_vl(b: &mut Bencher) {
-        let colag = Colag::default();
+        let colag = match Colag::for_bench() {
+            Some(colag) => colag,
+            None => return
+        };
         let env = Environment { domain: colag };
-        let mut speaker = UniformRandomSpeaker::new(&env.domain, 611);
-        let mut learner = RewardOnlyVL::new();
+        let mut speaker = UniformRandomSpeaker::new(&env.domain, Grammar(611)).unwrap();
+        let mut learner = RewardOnlyVL::new(env.domain.num_params());
         // let mut sentences: Vec<&Sentence> = speaker.take(5_000_000).collect();
         b.iter(|| learner.learn(&env, speaker.next().unwrap()));
     }
 
+    #[bench]
+    fn reward_only_vl_gibson_wexler(b: &mut Bencher) {
+        let env = Environment { domain: toy::gibson_wexler() };
+        let mut speaker = UniformRandomSpeaker::new(&env.domain, Grammar(4)).unwrap();
+        let mut learner = RewardOnlyVL::new(env.domain.num_params());
+        b.iter(|| learner.learn(&env, speaker.next().unwrap()));
+    }
+
     #[bench]
     fn reward_only_relevant_vl(b: &mut Bencher) {
-        let colag = Colag::default();
+        let colag = match Colag::for_bench() {
+            Some(colag) => colag,
+            None => return
+        };
         let env = Environment { domain: colag };
-        let mut speaker = UniformRandomSpeaker::new(&env.domain, 611);
-        let triggers = TriggerMap::from_file("./data/irrelevance-output.txt").unwrap();
-        let mut learner = RewardOnlyRelevantVL::new("normal", &triggers);
+        let mut speaker = UniformRandomSpeaker::new(&env.domain, Grammar(611)).unwrap();
+        let mut learner = RewardOnlyRelevantVL::new(&env.domain, "normal", 1.0).unwrap();
         // let mut sentences: Vec<&Sentence> = speaker.take(5_000_000).collect();
         b.iter(|| learner.learn(&env, speaker.next().unwrap()));
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use checkpoint;
+    use checkpoint::Checkpoint;
+    use domain::{LanguageDomain, DomainError, Grammar, Sentence};
+    use learner::{RewardOnlyRelevantVL, Learner, Environment};
+    use speaker::UniformRandomSpeaker;
+    use triggers::TriggerMap;
+    use toy;
+
+    // A learner and speaker built from `seed`, after `n` sentences.
+    fn run<'a>(env: &'a Environment, seed: u64, n: usize)
+               -> (RewardOnlyRelevantVL, UniformRandomSpeaker<'a>) {
+        checkpoint::seed_thread(seed);
+        let mut learner = RewardOnlyRelevantVL::new(&env.domain, "normal", 0.5).unwrap();
+        let mut speaker = UniformRandomSpeaker::new(&env.domain, Grammar(6)).unwrap();
+        for _ in 0..n {
+            let sent = speaker.next().unwrap();
+            learner.learn(env, sent);
+        }
+        (learner, speaker)
+    }
+
+    // The sentences heard over the next `n`, learning from each.
+    fn hear(env: &Environment, learner: &mut RewardOnlyRelevantVL,
+            speaker: &mut UniformRandomSpeaker, n: usize) -> Vec<Sentence> {
+        (0..n).map(|_| {
+            let sent = speaker.next().unwrap();
+            learner.learn(env, sent);
+            *sent
+        }).collect()
+    }
+
+    #[test]
+    fn a_restored_run_carries_on_as_if_never_stopped() {
+        let env = Environment { domain: toy::gibson_wexler() };
+        let (mut learner, mut speaker) = run(&env, 49, 300);
+        let (learner_state, speaker_state) = (learner.save(), speaker.save());
+
+        let (mut resumed_learner, mut resumed_speaker) = run(&env, 49, 0);
+        resumed_learner.restore(&learner_state).unwrap();
+        resumed_speaker.restore(&speaker_state).unwrap();
+
+        let heard = hear(&env, &mut learner, &mut speaker, 700);
+        assert_eq!(hear(&env, &mut resumed_learner, &mut resumed_speaker, 700), heard);
+        assert_eq!(resumed_learner.hypothesis.weights, learner.hypothesis.weights);
+        assert_eq!(resumed_learner.activated, learner.activated);
+        assert_eq!(resumed_learner.consumed, learner.consumed);
+        assert_eq!(learner.consumed, 1000);
+        assert!(learner.hypothesis.weights.iter().any(|w| *w != 0.5));
+    }
+
+    #[test]
+    fn relevant_vl_needs_a_complete_trigger_table() {
+        let mut domain = toy::gibson_wexler();
+        let learner = RewardOnlyRelevantVL::new(&domain, "normal", 0.5).unwrap();
+        assert_eq!(learner.to_string(), "RewardOnlyRelevant[normal:0.5]");
+        match RewardOnlyRelevantVL::new(&domain, "missing", 0.5) {
+            Err(DomainError::NoTriggerTable(ref name)) if name == "missing" => (),
+            _ => panic!("expected no trigger table")
+        }
+        let mut partial = TriggerMap::new();
+        let sentences = domain.all_sentences().clone();
+        for s in sentences[1..].iter() {
+            partial.insert(*s, domain.triggers(s).unwrap().clone());
+        }
+        domain.add_trigger_table("partial", partial);
+        match RewardOnlyRelevantVL::new(&domain, "partial", 0.5) {
+            Err(DomainError::NoTriggers(s)) if s == sentences[0] => (),
+            _ => panic!("expected no triggers for sentence {}", sentences[0])
+        }
+    }
+}
@@ -2,125 +2,715 @@
 
 extern crate rand;
 extern crate mersenne_twister;
+extern crate flate2;
+extern crate xz2;
 
-use std::thread;
-use std::sync::{Arc, Mutex};
+use std::io;
+use std::io::BufRead;
+use std::sync::{Mutex, MutexGuard};
+use std::collections::HashMap;
 use std::time::{SystemTime, Duration};
 
 use rand::Rng;
 
+mod bits;
+mod checkpoint;
 mod domain;
+mod evaluation;
+mod grammar;
 mod hypothesis;
 mod learner;
+mod param;
+mod population;
+mod relations;
+mod scheduler;
 mod sentence;
+mod snapshot;
 mod speaker;
+mod tla;
+mod toy;
+mod tree;
 mod triggers;
+mod validate;
 
-use domain::{Colag, LanguageDomain, Sentence, Grammar};
+use domain::{Colag, LanguageDomain, Sentence, Grammar, DomainError, COLAG_PARAMS};
+use tree::TreeId;
 use learner::{Learner, Environment};
-use hypothesis::{Theory};
-use speaker::{UniformRandomSpeaker};
-use triggers::{TriggerMap};
+use hypothesis::{Hypothesis, WeightedHypothesis, DistributionHypothesis};
+use evaluation::{Evaluation, Summary};
+use checkpoint::{Checkpoint, Experiment};
+use scheduler::Scheduler;
+use speaker::{Speaker, UniformRandomSpeaker, WeightedSpeaker, ZipfSpeaker, EmpiricalSpeaker,
+              ShuffledSpeaker, ReplaySpeaker, NoisySpeaker, Noise, MixtureSpeaker,
+              ScheduledSpeaker, Filter, TeacherSpeaker, Criterion};
+use tla::{TlaChain};
+use population::{Topology};
 
-type LearnerFactory = fn() -> Box<Learner>;
+type LearnerFactory = fn(&Environment) -> Box<dyn Learner>;
 
-fn learn_language<'a>(num_sentences: usize, env: &Environment, speaker: &mut UniformRandomSpeaker, learner: &mut Learner) -> usize {
-    for (consumed, sent) in speaker.into_iter().take(num_sentences).enumerate() {
+fn learn_language<'a, S: Speaker<'a> + ?Sized>(num_sentences: usize, env: &Environment, speaker: &mut S, learner: &mut dyn Learner) -> usize {
+    let mut consumed = 0;
+    while consumed < num_sentences {
+        speaker.observe(&learner.theory());
+        let sent = match speaker.next() {
+            Some(sent) => sent,
+            None => break
+        };
         learner.learn(env, sent);
+        consumed += 1;
         if learner.converged() {
-            return consumed + 1 as usize;
+            break;
         }
     }
-    num_sentences
+    consumed
+}
+
+// Returns the grammar in `targets` that is the fewest parameter flips away from
+// `guess`.
+fn closest_grammar(guess: &Grammar, targets: &[Grammar]) -> Grammar {
+    *targets.iter()
+        .min_by_key(|t| t.hamming(guess))
+        .expect("no target grammars")
 }
 
-fn learner_report(learner: &mut learner::Learner, target: &Grammar, name: &str, consumed: usize) {
+// `targets` are the grammars the learner heard. When there's more than one
+// the learner is reported against whichever is closest to its guess. Prints
+//   learner, target, guess, name, consumed, evaluation, theory
+// (see Evaluation for its columns) and returns the evaluation.
+fn learner_report(domain: &Colag, learner: &mut dyn learner::Learner, targets: &[Grammar],
+                  name: &str, consumed: usize) -> Evaluation {
     let guess = learner.guess();
-    match learner.theory() {
-        Theory::Simple(h) => println!("{}, {}, {}, {}, {}, {}", learner, target, guess, name, consumed, h),
-        Theory::Weighted(h) => println!("{}, {}, {}, {}, {}, {}", learner, target, guess, name, consumed, h)
-    }
+    let target = closest_grammar(&guess, targets);
+    let evaluation = Evaluation::new(domain, target, guess, &learner.theory());
+    println!("{}, {}, {}, {}, {}, {}, {}", learner, target, guess, name, consumed, evaluation,
+             learner.theory());
+    evaluation
 }
 
-fn watch_language<'a>(name: &str, num_sentences: usize, target: u16, env: &Environment, speaker: &mut UniformRandomSpeaker, learner: &mut learner::Learner) {
-    for (consumed, sent) in speaker.into_iter().take(num_sentences).enumerate() {
+fn watch_language<'a, S: Speaker<'a> + ?Sized>(name: &str, num_sentences: usize, target: Grammar, env: &Environment, speaker: &mut S, learner: &mut dyn learner::Learner) {
+    for consumed in 0..num_sentences {
+        speaker.observe(&learner.theory());
+        let sent = match speaker.next() {
+            Some(sent) => sent,
+            None => break
+        };
         learner.learn(env, sent);
         if learner.converged() || consumed == num_sentences - 1 {
-            learner_report(learner, &target, name, consumed);
+            learner_report(&env.domain, learner, &[target], name, consumed);
             break;
         }
         if consumed % 5000 != 0 {
             continue;
         }
-        learner_report(learner, &target, name, consumed);
+        learner_report(&env.domain, learner, &[target], name, consumed);
+    }
+}
+
+// Learns like learn_language, starting `consumed` sentences in, and calls
+// `save` with the number of sentences consumed every `interval` sentences.
+// Stops at the first checkpoint that can't be saved.
+fn learn_with_checkpoints<'a, S, F>(num_sentences: usize, mut consumed: usize, interval: usize,
+                                    env: &Environment, speaker: &mut S, learner: &mut dyn Learner,
+                                    mut save: F) -> io::Result<usize>
+    where S: Speaker<'a> + ?Sized, F: FnMut(usize, &dyn Learner, &S) -> io::Result<()> {
+    while consumed < num_sentences {
+        let chunk = std::cmp::min(interval, num_sentences - consumed);
+        let heard = learn_language(chunk, env, speaker, learner);
+        consumed += heard;
+        if heard < chunk || consumed == num_sentences || learner.converged() {
+            break;
+        }
+        save(consumed, learner, speaker)?;
     }
+    Ok(consumed)
 }
 
 fn to_secs(duration: Duration) -> f64 {
     duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
 }
-static LANGUAGES: [u16; 1] = [611];
-// static LANGUAGES: [u16; 1] = [611];
+static LANGUAGES: [Grammar; 1] = [Grammar(611)];
+// static LANGUAGES: [Grammar; 1] = [Grammar(611)];
+static RATES: [f64; 10] = [0., 0.1, 0.25, 0.3, 0.33, 0.4, 0.45, 0.5, 0.75, 1.0];
+const VL_SENTENCES: usize = 10_000_000;
+// how often a trial saves its progress, in sentences
+const CHECKPOINT_INTERVAL: usize = 100_000;
 
-fn vl_simulation(){
-    let env = Arc::new(Environment { domain: Colag::default() });
+// The experiment, even if a trial panicked while holding it. Every change to
+// it is a whole line appended to its log, so the others can carry on.
+fn lock<'a>(experiment: &'a Mutex<Experiment>) -> MutexGuard<'a, Experiment> {
+    experiment.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Trial number `trial` of `experiment`: a RewardOnlyRelevantVL using trigger
+// table `name` and irrelevance rate `rate` learns `target`. A trial that
+// already finished isn't run or reported again, only evaluated, and one that
+// got part way carries on from where it was saved. Returns the learner's name
+// and evaluation.
+//
+// A trial in progress is saved as `consumed | learner | speaker` and a
+// finished one as `consumed; guess; theory`.
+fn vl_trial(env: &Environment, experiment: &Mutex<Experiment>, trial: usize, target: Grammar,
+            name: &str, rate: f64) -> Result<(String, Evaluation), String> {
+    let (finished, progress) = {
+        let experiment = lock(experiment);
+        experiment.seed_trial(trial);
+        (experiment.finished(trial).cloned(), experiment.progress(trial).cloned())
+    };
+    let mut speaker = UniformRandomSpeaker::new(&env.domain, target).map_err(|e| e.to_string())?;
+    let mut learner = learner::RewardOnlyRelevantVL::new(&env.domain, name, rate)
+        .map_err(|e| e.to_string())?;
+    if let Some(result) = finished {
+        let fields = checkpoint::split(&result, 3)?;
+        let guess = fields[1].parse().map_err(|_| format!("Bad guess: {}", fields[1]))?;
+        let theory = hypothesis::parse(fields[2])?;
+        return Ok((learner.to_string(), Evaluation::new(&env.domain, target, guess, &*theory)));
+    }
+
+    let mut consumed = 0;
+    if let Some(state) = progress {
+        let fields: Vec<&str> = state.splitn(3, " | ").collect();
+        if fields.len() != 3 {
+            return Err(format!("Bad checkpoint: {}", state));
+        }
+        consumed = fields[0].parse().map_err(|_| format!("Bad count: {}", fields[0]))?;
+        learner.restore(fields[1])?;
+        speaker.restore(fields[2])?;
+    }
+    let consumed = learn_with_checkpoints(
+        VL_SENTENCES, consumed, CHECKPOINT_INTERVAL, env, &mut speaker, &mut learner,
+        |consumed, learner, speaker| {
+            let state = format!("{} | {} | {}", consumed, learner.save(), speaker.save());
+            lock(experiment).save(trial, state)
+        }).map_err(|e| format!("Couldn't save checkpoint: {}", e))?;
+    let evaluation = learner_report(&env.domain, &mut learner, &[target], "", consumed);
+    let result = checkpoint::join(&[consumed.to_string(), evaluation.guess.to_string(),
+                                    learner.theory().serialize()]);
+    lock(experiment).finish(trial, result).map_err(|e| e.to_string())?;
+    Ok((learner.to_string(), evaluation))
+}
+
+// Runs every target in LANGUAGES 100 times with each trigger table and rate,
+// each run a trial of its own on the scheduler. With a `checkpoint` file,
+// progress is saved there and a run that was stopped picks up where it left
+// off. Trials that fail, including by panicking, are reported as soon as they
+// do as
+//   failed, trial, target, table, rate, error
+// and left unfinished in the checkpoint, so resuming runs them again.
+fn vl_simulation(checkpoint: Option<&str>){
+    let env = Environment { domain: Colag::default() };
+    let experiment = match checkpoint {
+        Some(path) => Experiment::open(path).unwrap_or_else(|e| fail(format!("{}: {}", path, e))),
+        None => Experiment::new()
+    };
+    let experiment = Mutex::new(experiment);
     let maps = [
-        ("normal", TriggerMap::from_file("data/irrelevance-output.txt").unwrap()),
-        // ("equiv", TriggerMap::from_file("data/irrelevance-output-no-equiv.txt").unwrap()),
-        // ("super", TriggerMap::from_file("data/irrelevance-output-no-superset.txt").unwrap()),
+        triggers::NORMAL,
+        // triggers::NO_EQUIV,
+        // triggers::NO_SUPERSET,
     ];
-    let mut handles = Vec::new();
-    let maps = Arc::new(maps);
-    let mut languages: Vec<Grammar> = LANGUAGES.iter().cloned() //env.domain.language.keys() .cloned()
+    // numbered as before, (target copy, table, rate) in order
+    let trials: Vec<(Grammar, &str, f64)> = LANGUAGES.iter().cloned() //env.domain.language.keys() .cloned()
         .flat_map(|x| vec![x; 100])
+        .flat_map(|target| maps.iter().flat_map(move |name| {
+            RATES.iter().map(move |rate| (target, *name, *rate))
+        }))
         .collect();
-    let languages = Arc::new(Mutex::new(languages));
-    for thread_id in 0..4 {
-        let maps = maps.clone();
-        let env = env.clone();
-        let languages = languages.clone();
-        let mut iteration = 0;
-        handles.push(thread::spawn(move|| {
-            // for target in env.domain.language.keys() {
-            // for target in LANGUAGES.iter() {
-            loop {
-                let mut target = {
-                    if let Some(v) = languages.lock().unwrap().pop(){
-                        v
-                    } else {
-                        break;
-                    }
-                };
-                let mut speaker = UniformRandomSpeaker::new(&env.domain, target);
-                // let mut learner = learner::RewardOnlyVL::new();
-                // watch_language(&format!("{}:{}", &thread_id.to_string(), &iteration.to_string()),
-                //                10_000_000, target, &env, &mut speaker, &mut learner);
-                // iteration += 1;
-                for &(ref name, ref trigger_map) in maps.iter() {
-                    for rate in [0., 0.1, 0.25, 0.3, 0.33, 0.4, 0.45, 0.5, 0.75, 1.0].iter().cloned() {
-                        let mut learner = learner::RewardOnlyRelevantVL::new(name, &trigger_map, rate);
-                        // watch_language(&format!("{}:{}", &thread_id.to_string(), &iteration.to_string()),
-                        //                10_000_000, target, &env, &mut speaker, &mut learner);
-                        let consumed = learn_language(10_000_000, &env, &mut speaker, &mut learner);
-                        learner_report(&mut learner, &target, "", consumed);
-                        iteration += 1;
+    let results = Scheduler::new().run_reporting(trials.len(), |trial| {
+        let (target, name, rate) = trials[trial];
+        vl_trial(&env, &experiment, trial, target, name, rate)
+    }, |trial, result| if let Err(ref e) = *result {
+        let (target, name, rate) = trials[trial];
+        println!("failed, {}, {}, {}, {}, {}", trial, target, name, rate, e);
+    });
+
+    let mut summaries: HashMap<String, Summary> = HashMap::new();
+    for (learner, evaluation) in results.into_iter().filter_map(Result::ok) {
+        summaries.entry(learner)
+            .or_insert_with(|| Summary::new(env.domain.num_params()))
+            .add(&evaluation);
+    }
+    // summary, learner, then the columns of Summary
+    let mut names: Vec<&String> = summaries.keys().collect();
+    names.sort();
+    for name in names {
+        println!("summary, {}, {}", name, summaries[name]);
+    }
+}
+
+// Builds a speaker of `target` from its name: `uniform`, `zipf:EXPONENT`,
+// `empirical:FILENAME`, `shuffled`, `replay:FILENAME`, `mixture:G=W,G=W,...`,
+// `noisy:RATE:NOISE:SPEAKER`, where NOISE is a grammar, `random` or `pool`, or
+// `schedule:FILTER@UNTIL,...`, where FILTER is `words=N`, `dec` or `plain`, or
+// `teacher:triggers` or `teacher:failures`.
+// Replayed sentence files are looked up in `replays`.
+fn make_speaker<'a>(domain: &'a Colag, target: Grammar, name: &str,
+                    replays: &'a HashMap<String, Vec<Sentence>>)
+                    -> Result<Box<dyn Speaker<'a> + 'a>, String> {
+    let fields: Vec<&str> = name.splitn(2, ':').collect();
+    Ok(match fields.as_slice() {
+        ["uniform"] => Box::new(UniformRandomSpeaker::new(domain, target)
+                                .map_err(|e| e.to_string())?),
+        ["zipf", exponent] => {
+            let exponent = exponent.parse().map_err(|_| format!("Bad zipf exponent: {}", exponent))?;
+            Box::new(ZipfSpeaker::new(domain, target, exponent)?)
+        },
+        ["empirical", filename] => Box::new(EmpiricalSpeaker::from_file(domain, target, filename)
+                                            .map_err(|e| e.to_string())?),
+        ["shuffled"] => Box::new(ShuffledSpeaker::new(domain, target)?),
+        ["replay", filename] => match replays.get(*filename) {
+            Some(sentences) => Box::new(ReplaySpeaker::new(sentences, Some(target))),
+            None => return Err(format!("{} wasn't read", filename))
+        },
+        ["mixture", grammars] => {
+            let grammars = grammars.split(',')
+                .map(|gw| {
+                    let gw: Vec<&str> = gw.splitn(2, '=').collect();
+                    let weight = match gw.get(1) {
+                        Some(w) => w.parse().map_err(|_| format!("Bad mixture weight: {}", w))?,
+                        None => 1.
+                    };
+                    Ok((parse_grammar(gw[0])?, weight))
+                })
+                .collect::<Result<Vec<(Grammar, f64)>, String>>()?;
+            Box::new(MixtureSpeaker::new(domain, &grammars)?)
+        },
+        ["teacher", criterion] => {
+            let criterion = match *criterion {
+                "triggers" => Criterion::Triggers,
+                "failures" => Criterion::Failures,
+                _ => return Err(format!("Illegal speaker: {}", name))
+            };
+            Box::new(TeacherSpeaker::new(domain, target, criterion).map_err(|e| e.to_string())?)
+        },
+        ["schedule", stages] => {
+            let schedule = stages.split(',')
+                .map(|stage| {
+                    let stage: Vec<&str> = stage.splitn(2, '@').collect();
+                    if stage.len() != 2 {
+                        return Err(format!("Illegal schedule: {}", name));
                     }
-                }
-                // let mut learner = learner::RewardOnlyRelevantVL::new("super", &map);
-                // let consumed = learn_language(5_000_000, &env, &mut speaker, &mut learner);
-                // let guess = learner.guess();
-                // if let Theory::Weighted(weights) = learner.theory(){
-                //     println!("{}, {}, {}, {}, {}", learner, target, consumed, guess, weights);
-                // }
+                    let until = stage[1].parse()
+                        .map_err(|_| format!("Bad schedule length: {}", stage[1]))?;
+                    Ok((until, stage[0].parse()?))
+                })
+                .collect::<Result<Vec<(usize, Filter)>, String>>()?;
+            Box::new(ScheduledSpeaker::new(domain, target, &schedule)?)
+        },
+        ["noisy", rest] => {
+            let fields: Vec<&str> = rest.splitn(3, ':').collect();
+            if fields.len() != 3 {
+                return Err(format!("Illegal speaker: {}", name));
             }
-        }));
+            let noise = match fields[1] {
+                "random" => Noise::RandomGrammar,
+                "pool" => Noise::Pool,
+                g => Noise::Grammar(parse_grammar(g)?)
+            };
+            let rate = fields[0].parse().map_err(|_| format!("Bad noise rate: {}", fields[0]))?;
+            let speaker = make_speaker(domain, target, fields[2], replays)?;
+            Box::new(NoisySpeaker::new(domain, speaker, noise, rate).map_err(|e| e.to_string())?)
+        },
+        _ => return Err(format!("Illegal speaker: {}", name))
+    })
+}
+
+// The file a speaker named as for `make_speaker` replays, if it's a replay
+// speaker or a noisy one wrapped around one.
+fn replay_file(name: &str) -> Option<&str> {
+    let fields: Vec<&str> = name.splitn(2, ':').collect();
+    match fields.as_slice() {
+        ["replay", filename] => Some(filename),
+        ["noisy", rest] => rest.splitn(3, ':').nth(2).and_then(replay_file),
+        _ => None
+    }
+}
 
+// Learns `target` from each input distribution in turn (see `make_speaker`)
+// and reports how long the learner took to converge.
+fn input_simulation(factory: LearnerFactory, target: Grammar, speakers: &[String],
+                    num_sentences: usize) {
+    let env = Environment { domain: Colag::default() };
+    let mut replays = HashMap::new();
+    for name in speakers {
+        if let Some(filename) = replay_file(name) {
+            let sentences = speaker::read_sentences(filename).unwrap_or_else(|e| fail(e));
+            replays.insert(filename.to_string(), sentences);
+        }
+    }
+    for name in speakers {
+        let mut speaker = make_speaker(&env.domain, target, name, &replays)
+            .unwrap_or_else(|e| fail(e));
+        let mut learner = factory(&env);
+        let consumed = learn_language(num_sentences, &env, &mut *speaker, &mut *learner);
+        learner_report(&env.domain, &mut *learner, &speaker.components(), name, consumed);
+    }
+}
+
+// Samples `samples` legal grammars from `hypothesis` and returns the
+// distribution of them, or an error if it gives speaker::MAX_ATTEMPTS illegal
+// grammars in a row.
+fn grammar_distribution<R: Rng>(domain: &Colag, hypothesis: &dyn Hypothesis, samples: usize,
+                                rng: &mut R) -> Result<DistributionHypothesis, String> {
+    let mut counts: HashMap<Grammar, usize> = HashMap::new();
+    let mut sampled = 0;
+    let mut illegal = 0;
+    while sampled < samples {
+        let grammar = hypothesis.sample(rng);
+        if domain.is_legal(&grammar) {
+            *counts.entry(grammar).or_insert(0) += 1;
+            sampled += 1;
+            illegal = 0;
+        } else {
+            illegal += 1;
+            if illegal == speaker::MAX_ATTEMPTS {
+                return Err(format!("{} illegal grammars in a row from {}", illegal, hypothesis));
+            }
+        }
     }
-    for h in handles {
-        h.join();
+    DistributionHypothesis::new(domain.num_params(),
+                                counts.into_iter().map(|(g, n)| (g, n as f64)).collect())
+}
+
+// Iterated learning: the first generation learns from `target`, and every
+// learner after that learns from the previous generation's hypothesis. Prints
+// the grammar distribution of each generation as
+// generation, learner, grammar, share.
+// It stops early if a generation's hypothesis only gives illegal grammars.
+fn iterated_learning(factory: LearnerFactory, target: Grammar, generations: usize,
+                     num_sentences: usize) {
+    let env = Environment { domain: Colag::default() };
+    let mut rng = rand::thread_rng();
+    let mut teacher: Option<WeightedHypothesis> = None;
+    for generation in 0..generations {
+        let mut learner = factory(&env);
+        {
+            let speaker: Box<dyn Speaker> = match teacher {
+                None => Box::new(UniformRandomSpeaker::new(&env.domain, target)
+                                 .unwrap_or_else(|e| fail(e))),
+                Some(ref h) => Box::new(WeightedSpeaker::new(&env.domain, h.clone()))
+            };
+            for sent in speaker.take(num_sentences) {
+                learner.learn(&env, sent);
+            }
+        }
+        let hypothesis = learner.theory().weighted();
+        let distribution = match grammar_distribution(&env.domain, &hypothesis, 10_000, &mut rng) {
+            Ok(distribution) => distribution,
+            Err(e) => {
+                eprintln!("Generation {} can't speak: {}", generation, e);
+                return;
+            }
+        };
+        for &(grammar, share) in distribution.grammars().iter() {
+            println!("{}, {}, {}, {:.4}", generation, learner, grammar, share);
+        }
+        teacher = Some(hypothesis);
+    }
+}
+
+// Runs a population of `size` RewardOnlyVL agents on `topology`, starting from
+// `initial` grammars, and prints per round
+//   grammar, round, grammar, share of agents
+//   illegal, round, share of agents whose most likely grammar is illegal
+//   param, round, param, share of agents with it set, consensus
+fn population_simulation(size: usize, topology: Topology, rounds: usize, initial: &[Grammar]) {
+    let env = Environment { domain: Colag::default() };
+    let graph = topology.graph(size, &mut checkpoint::new_rng())
+        .unwrap_or_else(|e| fail(format!("Couldn't build the interaction graph: {}", e)));
+    let reports = population::simulate(&env, graph, initial, learner::RewardOnlyVL::boxed,
+                                       rounds, 100_000)
+        .unwrap_or_else(|e| fail(e));
+    for report in reports {
+        for &(grammar, share) in report.grammars.iter() {
+            println!("grammar, {}, {}, {:.4}", report.round, grammar, share);
+        }
+        println!("illegal, {}, {:.4}", report.round, report.illegal);
+        for param in 0..report.params.len() {
+            println!("param, {}, {}, {:.4}, {:.4}", report.round,
+                     param::label(param, report.params.len()),
+                     report.params[param], report.consensus(param));
+        }
+    }
+}
+
+// Loads a domain by name: `colag`, the Gibson & Wexler space `gw`, or
+// `synthetic:PARAMS:SENTENCES:AMBIGUITY:SUBSETS` (see toy::Synthetic).
+fn load_domain(name: &str) -> Colag {
+    let fields: Vec<&str> = name.split(':').collect();
+    match fields.as_slice() {
+        ["colag"] => Colag::default(),
+        ["gw"] => toy::gibson_wexler(),
+        ["synthetic", num_params, sentences, ambiguity, subsets] => {
+            let num_params = number_arg(num_params, "number of parameters");
+            if num_params < 1 || num_params > toy::MAX_SYNTHETIC_PARAMS {
+                fail(format!("A synthetic domain needs 1 to {} parameters, not {}",
+                             toy::MAX_SYNTHETIC_PARAMS, num_params));
+            }
+            let settings = toy::Synthetic {
+                num_params,
+                sentences: number_arg(sentences, "sentences per language"),
+                ambiguity: number_arg(ambiguity, "ambiguity"),
+                subsets: number_arg(subsets, "subset rate")
+            };
+            toy::synthetic(&mut rand::thread_rng(), &settings)
+        },
+        _ => fail(format!("Illegal domain: {}", name))
+    }
+}
+
+// how long a simulated TLA run gets to converge
+const TLA_SENTENCES: usize = 1_000_000;
+
+// Prints the exact TLA convergence analysis for `target`, one line per
+// starting grammar: grammar, local maximum?, P(converge), expected sentences.
+// With `runs`, each line ends with the share of that many TriggerLearner runs
+// that converged, to check the analysis against.
+fn tla_analysis(env: &Environment, target: Grammar, runs: Option<usize>) {
+    let chain = TlaChain::new(&env.domain, target).unwrap_or_else(|e| fail(e));
+    let probs = chain.convergence_probabilities();
+    let times = chain.expected_sentences();
+    if !probs.settled || !times.settled {
+        eprintln!("The analysis of {} didn't settle after {} iterations, so it's only approximate",
+                  target, tla::MAX_ITERATIONS);
+    }
+    let (probs, times) = (probs.values, times.values);
+    let maxima = chain.local_maxima();
+    for (n, grammar) in chain.states.iter().enumerate() {
+        let expected = match times[n] {
+            Some(t) => format!("{:.3}", t),
+            None => "inf".to_string()
+        };
+        let simulated = match runs {
+            Some(runs) => format!(", {:.6}", chain.simulate(env, *grammar, runs, TLA_SENTENCES)
+                                  .unwrap_or_else(|e| fail(e))),
+            None => String::new()
+        };
+        println!("{}, {}, {}, {:.6}, {}{}", target, grammar, maxima.contains(grammar),
+                 probs[n], expected, simulated);
+    }
+}
+
+// Prints the id and generating grammars of each surface form in `forms`, or
+// of each line of stdin if there are none.
+fn lookup_sentences(domain: &Colag, forms: &[String]) {
+    let lookup = |form: &str| match domain.lookup(form) {
+        Ok((sentence, grammars)) => {
+            let grammars: Vec<String> = grammars.iter().map(|g| g.to_string()).collect();
+            println!("{}, {}, {}", form.trim(), sentence, grammars.join(" "));
+        },
+        Err(e) => eprintln!("{}", e)
+    };
+    if forms.is_empty() {
+        let stdin = std::io::stdin();
+        for line in stdin.lock().lines() {
+            lookup(&line.unwrap_or_else(|e| fail(format!("Couldn't read stdin: {}", e))));
+        }
+    } else {
+        for form in forms.iter() {
+            lookup(form);
+        }
+    }
+}
+
+// Prints the structure `grammar` gives each surface form in `forms` as
+//   form, sentence, tree, grammar:sentence pairs with the same structure
+// followed, where the domain has the derivation, by
+//   derivation, tree, depth, leaves, derivation
+// Without a grammar, prints each structure shared by more than one pair as
+//   shared, tree, grammar:sentence pairs
+fn structure_report(domain: &Colag, grammar: Option<Grammar>, forms: &[String]) {
+    let pairs = |pairs: &[(Grammar, Sentence)]| pairs.iter()
+        .map(|&(g, s)| format!("{}:{}", g, s))
+        .collect::<Vec<String>>()
+        .join(" ");
+    let grammar = match grammar {
+        Some(grammar) => grammar,
+        None => {
+            let mut shared: Vec<(TreeId, Vec<(Grammar, Sentence)>)> =
+                domain.shared_structures().into_iter().collect();
+            shared.sort();
+            for (tree, shared) in shared {
+                println!("shared, {}, {}", tree, pairs(&shared));
+            }
+            return;
+        }
+    };
+    let describe = |form: &str| -> Result<(), DomainError> {
+        let (sentence, _) = domain.lookup(form)?;
+        let tree = domain.tree(&grammar, &sentence)?;
+        println!("{}, {}, {}, {}", form.trim(), sentence, tree,
+                 pairs(&domain.same_structure(&grammar, &sentence)?));
+        match domain.derivation(&grammar, &sentence) {
+            Ok(derivation) => println!("derivation, {}, {}, {}, {}", tree, derivation.depth(),
+                                       derivation.leaves().join(" "), derivation),
+            Err(DomainError::NoDerivation(_)) => (),
+            Err(e) => return Err(e)
+        }
+        Ok(())
+    };
+    for form in forms.iter() {
+        describe(form).unwrap_or_else(|e| eprintln!("{}", e));
+    }
+}
+
+// Prints `param, on, off, ambiguous, irrelevant` trigger shares for each
+// parameter of `target`, or `grammar, params` for every language with no
+// unambiguous trigger for some parameter if there's no target.
+fn trigger_report(domain: &Colag, table: &str, target: Option<Grammar>) {
+    match target {
+        Some(target) => {
+            let stats = triggers::param_stats(domain, table, &target)
+                .unwrap_or_else(|e| fail(e));
+            for (param, s) in stats.iter().enumerate() {
+                println!("{}, {:.4}, {:.4}, {:.4}, {:.4}", param::label(param, stats.len()),
+                         s.on, s.off, s.ambiguous, s.irrelevant);
+            }
+        },
+        None => {
+            for (grammar, params) in triggers::starved(domain, table)
+                .unwrap_or_else(|e| fail(e)) {
+                let params: Vec<String> = params.iter()
+                    .map(|&p| param::label(p, domain.num_params()))
+                    .collect();
+                println!("{}, {}", grammar, params.join(" "));
+            }
+        }
+    }
+}
+
+// Parses a grammar given as a number, a bit string or named settings (see
+// param::parse_grammar).
+fn parse_grammar(s: &str) -> Result<Grammar, String> {
+    param::parse_grammar(s).map_err(|e| format!("Illegal grammar {}: {}", s, e))
+}
+
+// Parses a grammar given on the command line, or exits.
+fn grammar_arg(s: &str) -> Grammar {
+    parse_grammar(s).unwrap_or_else(|e| fail(e))
+}
+
+// Parses a number given on the command line as `what`, or exits.
+fn number_arg<T: std::str::FromStr>(s: &str, what: &str) -> T {
+    s.parse().unwrap_or_else(|_| fail(format!("Bad {}: {}", what, s)))
+}
+
+// Reports `error` and exits.
+fn fail<E: std::fmt::Display>(error: E) -> ! {
+    eprintln!("{}", error);
+    std::process::exit(1)
+}
+
+// Prints `index, name, description` for each COLAG parameter, followed by
+// the setting of each in `grammar` if there is one.
+fn describe_params(grammar: Option<Grammar>) {
+    if let Some(g) = grammar {
+        println!("{}, {}, {}", g, g.bit_string(COLAG_PARAMS), param::named_settings(&g));
+    }
+    for p in param::PARAMS.iter() {
+        match grammar {
+            Some(g) => println!("{}, {}, {}, {}", p.index(), p, p.description(), p.is_set(&g) as u8),
+            None => println!("{}, {}, {}", p.index(), p, p.description())
+        }
     }
 }
 
 fn main(){
-    vl_simulation();
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|s| s.as_str()) {
+        Some("input") => {
+            let target = args.get(2).map_or(Grammar(611), |t| grammar_arg(t));
+            let speakers: Vec<String> = if args.len() > 3 {
+                args[3..].to_vec()
+            } else {
+                vec!["uniform".to_string()]
+            };
+            input_simulation(learner::RewardOnlyVL::boxed, target, &speakers, 10_000_000);
+        },
+        Some("iterated") => {
+            let target = args.get(2).map_or(Grammar(611), |t| grammar_arg(t));
+            let generations = args.get(3).map_or(10, |n| number_arg(n, "number of generations"));
+            iterated_learning(learner::RewardOnlyVL::boxed, target, generations, 1_000_000);
+        },
+        Some("population") => {
+            let size = args.get(2).map_or(100, |n| number_arg(n, "population size"));
+            let topology = args.get(3).map_or(Ok(Topology::FullyMixed), |t| t.parse())
+                .unwrap_or_else(|e| fail(e));
+            let rounds = args.get(4).map_or(10, |n| number_arg(n, "number of rounds"));
+            let initial: Vec<Grammar> = if args.len() > 5 {
+                args[5..].iter().map(|g| grammar_arg(g)).collect()
+            } else {
+                vec![Grammar(611)]
+            };
+            population_simulation(size, topology, rounds, &initial);
+        },
+        Some("validate") => {
+            // validate [IDS SENTENCES TRIGGERS...]
+            let mut files = validate::DataFiles::colag();
+            if args.len() > 4 {
+                files = validate::DataFiles {
+                    ids: &args[2],
+                    sentences: &args[3],
+                    triggers: args[4..].iter().map(|t| t.as_str()).collect()
+                };
+            }
+            let report = validate::validate(&files);
+            print!("{}", report);
+            if !report.ok() {
+                std::process::exit(1);
+            }
+        },
+        Some("lookup") => {
+            // lookup [SURFACE_FORM...]
+            lookup_sentences(&Colag::default(), &args[2..]);
+        },
+        Some("structure") => {
+            // structure shared|GRAMMAR SURFACE_FORM...
+            let grammar = match args.get(2).map_or("shared", |g| g.as_str()) {
+                "shared" => None,
+                g => Some(grammar_arg(g))
+            };
+            structure_report(&Colag::default(), grammar, args.get(3..).unwrap_or(&[]));
+        },
+        Some("relations") => {
+            // relations classes|subsets|hasse|dot [DOMAIN]
+            let domain = load_domain(args.get(3).map_or("colag", |d| d.as_str()));
+            let relations = relations::Relations::new(&domain)
+                .unwrap_or_else(|e| fail(format!("Couldn't relate languages: {}", e)));
+            let stdout = std::io::stdout();
+            let mut out = stdout.lock();
+            match args.get(2).map_or("classes", |t| t.as_str()) {
+                "classes" => relations.write_classes(&mut out),
+                "subsets" => relations.write_subsets(&mut out, false),
+                "hasse" => relations.write_subsets(&mut out, true),
+                "dot" => relations.write_dot(&mut out),
+                table => fail(format!("Unknown table: {}", table))
+            }.unwrap_or_else(|e| fail(format!("Couldn't write relations: {}", e)));
+        },
+        Some("params") => {
+            // params [GRAMMAR]
+            describe_params(args.get(2).map(|g| grammar_arg(g)));
+        },
+        Some("triggers") => {
+            // triggers TARGET|starved [TABLE [DOMAIN]]
+            let target = match args.get(2).map_or("611", |t| t.as_str()) {
+                "starved" => None,
+                t => Some(grammar_arg(t))
+            };
+            let table = args.get(3).map_or(triggers::NORMAL, |t| t.as_str());
+            let domain = load_domain(args.get(4).map_or("colag", |d| d.as_str()));
+            trigger_report(&domain, table, target);
+        },
+        Some("tla") => {
+            // tla [TARGET [DOMAIN [RUNS]]]
+            let target = args.get(2).map_or(Grammar(611), |t| grammar_arg(t));
+            let env = Environment { domain: load_domain(args.get(3).map_or("colag", |d| d.as_str())) };
+            let runs = args.get(4).map(|n| number_arg(n, "number of runs"));
+            tla_analysis(&env, target, runs);
+        },
+        Some("vl") => {
+            // vl [CHECKPOINT]
+            vl_simulation(args.get(2).map(|c| c.as_str()));
+        },
+        _ => vl_simulation(None)
+    }
 }
@@ -0,0 +1,192 @@
+use std::fmt;
+use std::str::FromStr;
+
+use domain::{Grammar, COLAG_PARAMS};
+use grammar;
+
+// The COLAG parameters (Sakas & Fodor 2012), in grammar bit order: SP is the
+// most significant of the 13 bits and QInv the least.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub enum Param {
+    SP,
+    HIP,
+    HCP,
+    OPT,
+    NS,
+    NT,
+    WHM,
+    PI,
+    TM,
+    VtoI,
+    ItoC,
+    AH,
+    QInv
+}
+
+pub static PARAMS: [Param; COLAG_PARAMS] = [
+    Param::SP, Param::HIP, Param::HCP, Param::OPT, Param::NS, Param::NT, Param::WHM,
+    Param::PI, Param::TM, Param::VtoI, Param::ItoC, Param::AH, Param::QInv
+];
+
+impl Param {
+    /// The parameter's position in a grammar, as used by `Grammar`.
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn from_index(n: usize) -> Option<Param> {
+        PARAMS.get(n).cloned()
+    }
+
+    /// True if `grammar` sets this parameter.
+    pub fn is_set(&self, grammar: &Grammar) -> bool {
+        grammar.get(self.index(), COLAG_PARAMS)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Param::SP => "SP",
+            Param::HIP => "HIP",
+            Param::HCP => "HCP",
+            Param::OPT => "OPT",
+            Param::NS => "NS",
+            Param::NT => "NT",
+            Param::WHM => "WHM",
+            Param::PI => "PI",
+            Param::TM => "TM",
+            Param::VtoI => "VtoI",
+            Param::ItoC => "ItoC",
+            Param::AH => "AH",
+            Param::QInv => "QInv"
+        }
+    }
+
+    /// What the parameter controls, and what setting it to 1 means.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            Param::SP => "Subject Position: subject initial (0) or final (1)",
+            Param::HIP => "Head in IP: IP head initial (0) or final (1)",
+            Param::HCP => "Head in CP: CP head initial (0) or final (1)",
+            Param::OPT => "Optional Topic: topic obligatory (0) or optional (1)",
+            Param::NS => "Null Subject: subjects overt (0) or can be dropped (1)",
+            Param::NT => "Null Topic: topics overt (0) or can be dropped (1)",
+            Param::WHM => "Wh-Movement: wh-words stay in situ (0) or move (1)",
+            Param::PI => "Pied Piping: prepositions strand (0) or are pied piped (1)",
+            Param::TM => "Topic Marking: topics unmarked (0) or marked (1)",
+            Param::VtoI => "V to I Movement: verbs stay (0) or raise to I (1)",
+            Param::ItoC => "I to C Movement: I stays (0) or raises to C (1)",
+            Param::AH => "Affix Hopping: absent (0) or present (1)",
+            Param::QInv => "Question Inversion: absent (0) or present (1)"
+        }
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Param {
+    type Err = String;
+    /// Parses a parameter name, in any case, or its index.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(n) = s.parse::<usize>() {
+            return Param::from_index(n).ok_or_else(|| format!("No parameter {}", n));
+        }
+        PARAMS.iter()
+            .find(|p| p.name().eq_ignore_ascii_case(s))
+            .cloned()
+            .ok_or_else(|| format!("Unknown parameter: {}", s))
+    }
+}
+
+/// The name of parameter `param` of a `num_params` parameter domain, or just
+/// its index outside COLAG.
+pub fn label(param: usize, num_params: usize) -> String {
+    match Param::from_index(param) {
+        Some(p) if num_params == COLAG_PARAMS => p.name().to_string(),
+        _ => param.to_string()
+    }
+}
+
+/// Writes a COLAG grammar as the names of the parameters it sets joined with
+/// `+`, like `OPT+WHM+PI+AH+QInv` for English, or `none`.
+pub fn named_settings(grammar: &Grammar) -> String {
+    let names: Vec<&str> = PARAMS.iter()
+        .filter(|p| p.is_set(grammar))
+        .map(|p| p.name())
+        .collect();
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join("+")
+    }
+}
+
+/// Parses a COLAG grammar written as a number, a 13 bit string or named
+/// settings, as written by `Grammar::bit_string` and `named_settings`.
+/// Whether the grammar is legal is up to the domain.
+pub fn parse_grammar(s: &str) -> Result<Grammar, String> {
+    if let Ok(grammar) = grammar::parse_bits(s, COLAG_PARAMS) {
+        return Ok(grammar);
+    }
+    if let Ok(grammar) = s.parse::<Grammar>() {
+        if !grammar.fits(COLAG_PARAMS) {
+            return Err(format!("{} has more than {} parameters", grammar, COLAG_PARAMS));
+        }
+        return Ok(grammar);
+    }
+    if s == "none" {
+        return Ok(Grammar(0));
+    }
+    let mut grammar = Grammar(0);
+    for name in s.split('+') {
+        grammar = grammar.set(name.parse::<Param>()?.index(), COLAG_PARAMS);
+    }
+    Ok(grammar)
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::{Grammar, COLAG_PARAMS};
+    use param;
+    use param::{Param, PARAMS};
+
+    #[test]
+    fn params_follow_the_grammar_bit_order() {
+        for (n, p) in PARAMS.iter().enumerate() {
+            assert_eq!(p.index(), n);
+            assert_eq!(Param::from_index(n), Some(*p));
+            assert!(p.is_set(&Grammar(0).set(n, COLAG_PARAMS)));
+            assert_eq!(p.to_string().parse::<Param>(), Ok(*p));
+        }
+        assert!(Param::SP.is_set(&Grammar(1 << 12)));
+        assert!(Param::QInv.is_set(&Grammar(1)));
+        assert_eq!("qinv".parse::<Param>(), Ok(Param::QInv));
+        assert!("13".parse::<Param>().is_err());
+    }
+
+    #[test]
+    fn grammars_parse_back_from_how_they_are_written() {
+        // English
+        assert_eq!(param::named_settings(&Grammar(611)), "OPT+WHM+PI+AH+QInv");
+        assert_eq!(Grammar(611).bit_string(COLAG_PARAMS), "0001001100011");
+        for g in (0..1 << COLAG_PARAMS).step_by(7).chain(vec![0, 611, (1 << COLAG_PARAMS) - 1]) {
+            let g = Grammar(g);
+            assert_eq!(param::parse_grammar(&g.to_string()), Ok(g));
+            assert_eq!(param::parse_grammar(&g.bit_string(COLAG_PARAMS)), Ok(g));
+            assert_eq!(param::parse_grammar(&param::named_settings(&g)), Ok(g));
+        }
+        assert_eq!(param::parse_grammar("none"), Ok(Grammar(0)));
+        assert_eq!(param::parse_grammar("qinv+OPT"), Ok(Grammar(1 << 9 | 1)));
+    }
+
+    #[test]
+    fn grammars_out_of_range_are_rejected() {
+        assert!(param::parse_grammar("8192").is_err());
+        assert!(param::parse_grammar("99999").is_err());
+        assert!(param::parse_grammar("OPT+Nope").is_err());
+        assert!(param::parse_grammar("").is_err());
+    }
+}
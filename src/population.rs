@@ -0,0 +1,279 @@
+extern crate csv;
+
+use std::error::Error;
+use std::str::FromStr;
+use std::collections::{HashMap, HashSet};
+
+use rand::Rng;
+
+use checkpoint;
+use checkpoint::ResumableRng;
+use domain::{Colag, Grammar, Sentence, LanguageDomain, DomainError};
+use hypothesis::{Hypothesis, WeightedHypothesis};
+use learner::{Learner, Environment};
+use scheduler::Scheduler;
+use speaker::{WeightedSpeaker};
+
+// How agents in a population are connected. Agents only hear sentences from
+// their neighbours.
+pub enum Topology {
+    FullyMixed,
+    // every agent hears the k agents on either side of it
+    Ring(usize),
+    // a ring of k neighbours a side with each edge rewired with probability p
+    // (Watts & Strogatz 1998)
+    SmallWorld(usize, f64),
+    // whitespace separated pairs of agent indices, one undirected edge a line
+    EdgeList(String)
+}
+
+impl Topology {
+    /// Returns the neighbours of each of the `size` agents.
+    pub fn graph<R: Rng>(&self, size: usize, rng: &mut R)
+                         -> Result<Vec<Vec<usize>>, Box<dyn Error>> {
+        let mut edges: Vec<HashSet<usize>> = vec![HashSet::new(); size];
+        match *self {
+            Topology::FullyMixed => {
+                for a in 0..size {
+                    edges[a].extend((0..size).filter(|&b| b != a));
+                }
+            },
+            Topology::Ring(k) => {
+                for a in 0..size {
+                    for offset in 1..(k + 1) {
+                        connect(&mut edges, a, (a + offset) % size);
+                    }
+                }
+            },
+            Topology::SmallWorld(k, p) => {
+                for a in 0..size {
+                    for offset in 1..(k + 1) {
+                        let mut b = (a + offset) % size;
+                        if rng.next_f64() < p {
+                            // rewire to an agent we aren't already connected to
+                            let candidates: Vec<usize> = (0..size)
+                                .filter(|&c| c != a && !edges[a].contains(&c))
+                                .collect();
+                            if let Some(&c) = rng.choose(&candidates) {
+                                b = c;
+                            }
+                        }
+                        connect(&mut edges, a, b);
+                    }
+                }
+            },
+            Topology::EdgeList(ref filename) => {
+                let mut rdr = csv::ReaderBuilder::new()
+                    .delimiter(b' ')
+                    .has_headers(false)
+                    .from_path(filename)?;
+                for result in rdr.deserialize() {
+                    let (a, b): (usize, usize) = result?;
+                    if a >= size || b >= size {
+                        return Err(From::from(format!("edge {} {} in {} is outside a population of {}",
+                                                      a, b, filename, size)));
+                    }
+                    connect(&mut edges, a, b);
+                }
+            }
+        }
+        Ok(edges.into_iter()
+           .map(|neighbors| {
+               let mut neighbors: Vec<usize> = neighbors.into_iter().collect();
+               neighbors.sort();
+               neighbors
+           })
+           .collect())
+    }
+}
+
+impl FromStr for Topology {
+    type Err = String;
+    /// Parses `mixed`, `ring:K`, `smallworld:K:P` or `edges:FILENAME`. The
+    /// filename is everything after `edges:`, colons and all.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.splitn(2, ':');
+        let (kind, rest) = (fields.next().unwrap_or(""), fields.next());
+        let bad = || format!("Illegal topology: {}", s);
+        match (kind, rest) {
+            ("mixed", None) => Ok(Topology::FullyMixed),
+            ("ring", Some(k)) => Ok(Topology::Ring(k.parse().map_err(|_| bad())?)),
+            ("smallworld", Some(rest)) => match rest.splitn(2, ':').collect::<Vec<&str>>().as_slice() {
+                [k, p] => Ok(Topology::SmallWorld(k.parse().map_err(|_| bad())?,
+                                                  p.parse().map_err(|_| bad())?)),
+                _ => Err(bad())
+            },
+            ("edges", Some(filename)) if !filename.is_empty() =>
+                Ok(Topology::EdgeList(filename.to_string())),
+            _ => Err(bad())
+        }
+    }
+}
+
+fn connect(edges: &mut Vec<HashSet<usize>>, a: usize, b: usize) {
+    if a != b {
+        edges[a].insert(b);
+        edges[b].insert(a);
+    }
+}
+
+// Speaks a mixture of the languages of an agent's neighbours: each utterance
+// comes from a neighbour chosen uniformly at random. A neighbour that falls
+// silent (see WeightedSpeaker) isn't asked again, and the speaker only falls
+// silent itself once all of them have.
+struct NeighborhoodSpeaker<'a> {
+    speakers: Vec<WeightedSpeaker<'a>>,
+    rng: ResumableRng
+}
+
+impl<'a> Iterator for NeighborhoodSpeaker<'a> {
+    type Item = &'a Sentence;
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.speakers.is_empty() {
+            let n = self.rng.gen_range(0, self.speakers.len());
+            match self.speakers[n].next() {
+                Some(sent) => return Some(sent),
+                None => { self.speakers.swap_remove(n); }
+            }
+        }
+        None
+    }
+}
+
+// The per-round measurements of a population.
+pub struct RoundReport {
+    pub round: usize,
+    // share of agents whose most likely grammar is each legal grammar, most
+    // common first
+    pub grammars: Vec<(Grammar, f64)>,
+    // share of agents whose most likely grammar isn't legal in the domain
+    pub illegal: f64,
+    // share of agents with each parameter set
+    pub params: Vec<f64>
+}
+
+impl RoundReport {
+    fn new(round: usize, hypotheses: &[WeightedHypothesis], domain: &Colag) -> Self {
+        let num_params = domain.num_params();
+        let mut counts: HashMap<Grammar, usize> = HashMap::new();
+        let mut illegal = 0.;
+        let mut params = vec![0.; num_params];
+        let share = 1. / hypotheses.len() as f64;
+        for h in hypotheses.iter() {
+            let grammar = h.most_likely();
+            if domain.is_legal(&grammar) {
+                *counts.entry(grammar).or_insert(0) += 1;
+            } else {
+                illegal += share;
+            }
+            for param in 0..num_params {
+                params[param] += grammar.get(param, num_params) as u8 as f64 * share;
+            }
+        }
+        let mut grammars: Vec<(Grammar, f64)> = counts.into_iter()
+            .map(|(g, n)| (g, n as f64 * share))
+            .collect();
+        grammars.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+        RoundReport { round, grammars, illegal, params }
+    }
+
+    /// Agreement on `param`, from 0.5 (evenly split) to 1 (unanimous).
+    pub fn consensus(&self, param: usize) -> f64 {
+        self.params[param].max(1. - self.params[param])
+    }
+}
+
+// Each round every agent is replaced by a fresh learner from `factory` that
+// hears `num_sentences` sentences from its neighbours, as they were at the end
+// of the previous round. Agents start out speaking `initial` grammars, handed
+// out in turn. Agents learn on the scheduler, and a round in which any of
+// them fails ends the simulation with its error.
+pub fn simulate(env: &Environment, graph: Vec<Vec<usize>>, initial: &[Grammar],
+                factory: fn(&Environment) -> Box<dyn Learner>, rounds: usize,
+                num_sentences: usize) -> Result<Vec<RoundReport>, Box<dyn Error>> {
+    if initial.is_empty() {
+        return Err(From::from("a population needs at least one initial grammar"));
+    }
+    for grammar in initial.iter() {
+        if !env.domain.is_legal(grammar) {
+            return Err(Box::new(DomainError::IllegalGrammar(*grammar)));
+        }
+    }
+    let num_params = env.domain.num_params();
+    let mut hypotheses: Vec<WeightedHypothesis> = (0..graph.len())
+        .map(|n| WeightedHypothesis::from_grammar(&initial[n % initial.len()], num_params))
+        .collect();
+    let scheduler = Scheduler::new();
+    let mut reports = vec![RoundReport::new(0, &hypotheses, &env.domain)];
+    for round in 1..(rounds + 1) {
+        let teachers = &hypotheses;
+        let learned = scheduler.run(graph.len(), |agent| {
+            let mut learner = factory(env);
+            if !graph[agent].is_empty() {
+                let speaker = NeighborhoodSpeaker {
+                    speakers: graph[agent].iter()
+                        .map(|&n| WeightedSpeaker::new(&env.domain, teachers[n].clone()))
+                        .collect(),
+                    rng: checkpoint::new_rng()
+                };
+                for sent in speaker.take(num_sentences) {
+                    learner.learn(env, sent);
+                }
+            }
+            Ok(learner.theory().weighted())
+        });
+        hypotheses = learned.into_iter()
+            .enumerate()
+            .map(|(agent, result)| result
+                 .map_err(|e| format!("agent {} failed in round {}: {}", agent, round, e)))
+            .collect::<Result<Vec<WeightedHypothesis>, String>>()?;
+        reports.push(RoundReport::new(round, &hypotheses, &env.domain));
+    }
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use checkpoint;
+    use domain::{Colag, Grammar};
+    use hypothesis::WeightedHypothesis;
+    use population::{Topology, NeighborhoodSpeaker};
+    use speaker::WeightedSpeaker;
+
+    #[test]
+    fn topologies_parse() {
+        match "smallworld:4:0.25".parse() {
+            Ok(Topology::SmallWorld(4, p)) => assert_eq!(p, 0.25),
+            _ => panic!("expected a small world")
+        }
+        match "edges:C:\\graphs\\a:b.txt".parse() {
+            Ok(Topology::EdgeList(ref filename)) => assert_eq!(filename, "C:\\graphs\\a:b.txt"),
+            _ => panic!("expected an edge list")
+        }
+        for bad in ["", "mixed:1", "ring", "ring:x", "smallworld:4", "edges:", "star"].iter() {
+            assert!(bad.parse::<Topology>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn silent_neighbours_are_skipped() {
+        // only grammar 0 is legal, so a teacher sure of 11 can't say anything
+        let mut language = HashMap::new();
+        language.insert(Grammar(0), (1..3).collect::<HashSet<_>>());
+        let domain = Colag::from_languages(2, language);
+        let speaker = |weights: Vec<f64>| WeightedSpeaker::new(&domain, WeightedHypothesis { weights });
+
+        checkpoint::seed_thread(28);
+        let mixed = NeighborhoodSpeaker {
+            speakers: vec![speaker(vec![1., 1.]), speaker(vec![0., 0.]), speaker(vec![1., 1.])],
+            rng: checkpoint::new_rng()
+        };
+        assert_eq!(mixed.take(100).count(), 100);
+        let mut silent = NeighborhoodSpeaker {
+            speakers: vec![speaker(vec![1., 1.])],
+            rng: checkpoint::new_rng()
+        };
+        assert_eq!(silent.next(), None);
+    }
+}
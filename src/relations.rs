@@ -0,0 +1,221 @@
+use std::io;
+use std::io::Write;
+use std::fmt;
+use std::collections::{HashMap, HashSet};
+
+use domain::{LanguageDomain, Grammar, Sentence, DomainError};
+
+// How the language of one grammar stands to that of another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Relation {
+    Equivalent,
+    ProperSubset,
+    ProperSuperset,
+    Overlapping,
+    Disjoint
+}
+
+pub static RELATIONS: [Relation; 5] = [
+    Relation::Equivalent, Relation::ProperSubset, Relation::ProperSuperset,
+    Relation::Overlapping, Relation::Disjoint
+];
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match *self {
+            Relation::Equivalent => "equivalent",
+            Relation::ProperSubset => "subset",
+            Relation::ProperSuperset => "superset",
+            Relation::Overlapping => "overlapping",
+            Relation::Disjoint => "disjoint"
+        })
+    }
+}
+
+/// How the language of `a` stands to that of `b`, worked out from the two
+/// languages alone rather than by relating the whole domain.
+pub fn relation<D: LanguageDomain>(domain: &D, a: &Grammar, b: &Grammar) -> Result<Relation, DomainError> {
+    let (a, b) = (domain.language(a)?, domain.language(b)?);
+    Ok(if a == b {
+        Relation::Equivalent
+    } else if a.is_subset(b) {
+        Relation::ProperSubset
+    } else if b.is_subset(a) {
+        Relation::ProperSuperset
+    } else if a.is_disjoint(b) {
+        Relation::Disjoint
+    } else {
+        Relation::Overlapping
+    })
+}
+
+// The extensional relations between the languages of a domain. Grammars with
+// identical languages are grouped into equivalence classes, each named after
+// its smallest grammar, and subsets are worked out between classes.
+pub struct Relations {
+    // grammars of each class in order, classes in order of their names
+    pub classes: Vec<Vec<Grammar>>,
+    language: Vec<HashSet<Sentence>>,
+    // the classes whose languages are proper subsets (supersets) of each
+    // class's, in order
+    subsets: Vec<Vec<usize>>,
+    supersets: Vec<Vec<usize>>
+}
+
+impl Relations {
+    pub fn new<D: LanguageDomain>(domain: &D) -> Result<Relations, DomainError> {
+        let mut by_language: HashMap<Vec<Sentence>, Vec<Grammar>> = HashMap::new();
+        for grammar in domain.grammars().iter() {
+            let mut sentences = domain.language_vec(grammar)?.clone();
+            sentences.sort();
+            by_language.entry(sentences).or_insert_with(Vec::new).push(*grammar);
+        }
+        let mut classes: Vec<(Vec<Grammar>, HashSet<Sentence>)> = by_language.into_iter()
+            .map(|(sentences, mut grammars)| {
+                grammars.sort();
+                (grammars, sentences.into_iter().collect())
+            })
+            .collect();
+        classes.sort_by_key(|&(ref grammars, _)| grammars[0]);
+        let (classes, language): (Vec<Vec<Grammar>>, Vec<HashSet<Sentence>>) =
+            classes.into_iter().unzip();
+
+        let mut subsets = vec![Vec::new(); classes.len()];
+        let mut supersets = vec![Vec::new(); classes.len()];
+        for a in 0..classes.len() {
+            for b in 0..classes.len() {
+                // languages of different classes differ, so equal sizes can't
+                // be subsets
+                if language[a].len() < language[b].len() && language[a].is_subset(&language[b]) {
+                    subsets[b].push(a);
+                    supersets[a].push(b);
+                }
+            }
+        }
+
+        Ok(Relations { classes, language, subsets, supersets })
+    }
+
+    /// The edges of the Hasse diagram, (subset, superset) pairs of classes with
+    /// no class in between.
+    pub fn covers(&self) -> Vec<(usize, usize)> {
+        let mut covers = Vec::new();
+        for a in 0..self.classes.len() {
+            let above: HashSet<&usize> = self.supersets[a].iter().collect();
+            for b in self.supersets[a].iter() {
+                if !self.subsets[*b].iter().any(|c| above.contains(c)) {
+                    covers.push((a, *b));
+                }
+            }
+        }
+        covers
+    }
+
+    /// Writes `class, size, sentences, grammars` for each equivalence class.
+    pub fn write_classes<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for (n, grammars) in self.classes.iter().enumerate() {
+            writeln!(out, "{}, {}, {}, {}", grammars[0], grammars.len(), self.language[n].len(),
+                     join(grammars.iter()))?;
+        }
+        Ok(())
+    }
+
+    /// Writes `subset, superset` for each pair of classes in a proper subset
+    /// relation, or only the covering pairs if `hasse`.
+    pub fn write_subsets<W: Write>(&self, out: &mut W, hasse: bool) -> io::Result<()> {
+        let pairs: Vec<(usize, usize)> = if hasse {
+            self.covers()
+        } else {
+            (0..self.classes.len())
+                .flat_map(|a| self.supersets[a].iter().map(move |b| (a, *b)))
+                .collect()
+        };
+        for (a, b) in pairs {
+            writeln!(out, "{}, {}", self.classes[a][0], self.classes[b][0])?;
+        }
+        Ok(())
+    }
+
+    /// Writes the Hasse diagram of the subset relation in Graphviz DOT, subsets
+    /// below their supersets. Classes unrelated to any other are left out.
+    pub fn write_dot<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "digraph subsets {{")?;
+        writeln!(out, "    rankdir=BT;")?;
+        writeln!(out, "    node [shape=box];")?;
+        for (n, grammars) in self.classes.iter().enumerate() {
+            if self.subsets[n].is_empty() && self.supersets[n].is_empty() {
+                continue;
+            }
+            writeln!(out, "    {} [label=\"{}\\n{} sentences\"];", grammars[0],
+                     join(grammars.iter()), self.language[n].len())?;
+        }
+        for (a, b) in self.covers() {
+            writeln!(out, "    {} -> {};", self.classes[a][0], self.classes[b][0])?;
+        }
+        writeln!(out, "}}")
+    }
+}
+
+fn join<'a, I: Iterator<Item=&'a Grammar>>(grammars: I) -> String {
+    grammars.map(|g| g.to_string()).collect::<Vec<String>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use domain::{Colag, Grammar, Sentence};
+    use relations;
+    use relations::{Relations, Relation};
+
+    // 0 and 1 speak the same language, inside 2's, inside 3's; 4 overlaps 2
+    // and 3 but not 0, and 5 shares nothing with anyone.
+    fn domain() -> Colag {
+        let languages: [&[Sentence]; 6] = [&[1, 2], &[2, 1], &[1, 2, 3], &[1, 2, 3, 4], &[3, 5],
+                                           &[6]];
+        let language: HashMap<Grammar, HashSet<Sentence>> = languages.iter()
+            .enumerate()
+            .map(|(g, sentences)| (Grammar(g as u32), sentences.iter().cloned().collect()))
+            .collect();
+        Colag::from_languages(3, language)
+    }
+
+    #[test]
+    fn relates_pairs_of_languages() {
+        let domain = domain();
+        let expected = [
+            (0, 1, Relation::Equivalent),
+            (0, 2, Relation::ProperSubset),
+            (0, 3, Relation::ProperSubset),
+            (3, 1, Relation::ProperSuperset),
+            (2, 4, Relation::Overlapping),
+            (4, 3, Relation::Overlapping),
+            (0, 4, Relation::Disjoint),
+            (5, 3, Relation::Disjoint),
+            (5, 5, Relation::Equivalent),
+        ];
+        for &(a, b, relation) in expected.iter() {
+            assert_eq!(relations::relation(&domain, &Grammar(a), &Grammar(b)).unwrap(), relation,
+                       "{} to {}", a, b);
+        }
+        assert!(relations::relation(&domain, &Grammar(0), &Grammar(7)).is_err());
+    }
+
+    #[test]
+    fn groups_classes_and_covers() {
+        let relations = Relations::new(&domain()).unwrap();
+        let classes: Vec<Vec<Grammar>> = vec![vec![0, 1], vec![2], vec![3], vec![4], vec![5]]
+            .into_iter()
+            .map(|class| class.into_iter().map(Grammar).collect())
+            .collect();
+        assert_eq!(relations.classes, classes);
+        // grammar 0's class is inside grammar 3's only by way of grammar 2's
+        assert_eq!(relations.covers(), vec![(0, 1), (1, 2)]);
+        let written = |hasse| {
+            let mut out = Vec::new();
+            relations.write_subsets(&mut out, hasse).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(written(false), "0, 2\n0, 3\n2, 3\n");
+        assert_eq!(written(true), "0, 2\n2, 3\n");
+    }
+}
@@ -0,0 +1,142 @@
+use std::any::Any;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::thread;
+use std::sync::Mutex;
+use std::collections::VecDeque;
+
+// Runs numbered jobs on one worker thread per core. Each worker starts with a
+// contiguous block of the jobs and takes them from the front; a worker that
+// runs out steals from the back of another's block, so long jobs don't leave
+// the other workers idle.
+pub struct Scheduler {
+    workers: usize
+}
+
+impl Scheduler {
+    /// A scheduler with as many workers as there are cores.
+    pub fn new() -> Self {
+        Scheduler::with_workers(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+
+    /// A scheduler with `workers` workers, or one if that's 0.
+    pub fn with_workers(workers: usize) -> Self {
+        Scheduler { workers: workers.max(1) }
+    }
+
+    /// Runs `job` on each of `0..jobs` and returns the results in order. A
+    /// job that panics fails with the panic's message, and the others carry
+    /// on.
+    pub fn run<T, F>(&self, jobs: usize, job: F) -> Vec<Result<T, String>>
+        where T: Send, F: Fn(usize) -> Result<T, String> + Sync {
+        self.run_reporting(jobs, job, |_, _| ())
+    }
+
+    /// Like `run`, and calls `report` with each job's number and result as
+    /// soon as it finishes, on the worker that ran it.
+    pub fn run_reporting<T, F, R>(&self, jobs: usize, job: F, report: R) -> Vec<Result<T, String>>
+        where T: Send, F: Fn(usize) -> Result<T, String> + Sync,
+              R: Fn(usize, &Result<T, String>) + Sync {
+        let workers = self.workers.min(jobs).max(1);
+        let queues: Vec<Mutex<VecDeque<usize>>> = (0..workers)
+            .map(|w| Mutex::new((w * jobs / workers..(w + 1) * jobs / workers).collect()))
+            .collect();
+        let mut results: Vec<(usize, Result<T, String>)> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|w| {
+                    let (queues, job, report) = (&queues, &job, &report);
+                    scope.spawn(move || {
+                        let mut results = Vec::new();
+                        while let Some(n) = next_job(queues, w) {
+                            let result = panic::catch_unwind(AssertUnwindSafe(|| job(n)))
+                                .unwrap_or_else(|e| Err(panic_message(e)));
+                            report(n, &result);
+                            results.push((n, result));
+                        }
+                        results
+                    })
+                })
+                .collect();
+            handles.into_iter()
+                .flat_map(|h| h.join().expect("scheduler worker died"))
+                .collect()
+        });
+        results.sort_by_key(|&(n, _)| n);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
+
+// The next job of worker `w`: its own next one, or else one stolen from the
+// back of the first other worker that has any left.
+fn next_job(queues: &[Mutex<VecDeque<usize>>], w: usize) -> Option<usize> {
+    if let Some(n) = queues[w].lock().unwrap().pop_front() {
+        return Some(n);
+    }
+    (1..queues.len())
+        .map(|i| (w + i) % queues.len())
+        .filter_map(|victim| queues[victim].lock().unwrap().pop_back())
+        .next()
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "panicked".to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::Duration;
+    use scheduler::Scheduler;
+
+    #[test]
+    fn results_come_back_in_order() {
+        for workers in 1..5 {
+            // later jobs finish first, so workers finish out of order
+            let results = Scheduler::with_workers(workers).run(20, |n| {
+                thread::sleep(Duration::from_millis(20 - n as u64));
+                Ok(n * n)
+            });
+            let squares: Vec<Result<usize, String>> = (0..20).map(|n| Ok(n * n)).collect();
+            assert_eq!(results, squares);
+        }
+    }
+
+    #[test]
+    fn a_panicking_job_fails_and_the_others_finish() {
+        let reported = Mutex::new(Vec::new());
+        let results = Scheduler::with_workers(3).run_reporting(10, |n| {
+            match n {
+                3 => panic!("job {} fell over", n),
+                7 => Err("job 7 failed".to_string()),
+                _ => Ok(n)
+            }
+        }, |n, result| reported.lock().unwrap().push((n, result.is_ok())));
+        for (n, result) in results.iter().enumerate() {
+            match n {
+                3 => assert_eq!(result, &Err("job 3 fell over".to_string())),
+                7 => assert_eq!(result, &Err("job 7 failed".to_string())),
+                _ => assert_eq!(result, &Ok(n))
+            }
+        }
+        let mut reported = reported.into_inner().unwrap();
+        reported.sort();
+        assert_eq!(reported, (0..10).map(|n| (n, n != 3 && n != 7)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn runs_no_jobs_and_fewer_jobs_than_workers() {
+        let none = Scheduler::with_workers(4).run(0, |n| Ok(n));
+        assert!(none.is_empty());
+        let few = Scheduler::with_workers(8).run(3, |n| Ok(n));
+        assert_eq!(few, vec![Ok(0), Ok(1), Ok(2)]);
+        assert_eq!(Scheduler::with_workers(0).run(2, |n| Ok(n)), vec![Ok(0), Ok(1)]);
+        assert!(Scheduler::new().workers >= 1);
+    }
+}
@@ -78,6 +78,19 @@ pub static S: SurfaceSymbol = SurfaceSymbol::S_ { wh: FeatureVal::Any, wa: Featu
 
 use self::SurfaceSymbol::*;
 
+use std::convert::TryFrom;
+use std::fmt;
+
+// A word, illocution or trigger that isn't part of the COLAG vocabulary.
+#[derive(Debug, PartialEq)]
+pub struct UnknownToken(pub String);
+
+impl fmt::Display for UnknownToken {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      write!(f, "unknown token `{}`", self.0)
+  }
+}
+
 impl SurfaceSymbol {
   pub fn has_feature(&self, feature: &FeatureType) -> bool {
       match self {
@@ -98,9 +111,10 @@ impl SurfaceSymbol {
   }
 }
 
-impl<'a> From<&'a str> for SurfaceSymbol {
-  fn from(s: &'a str) -> SurfaceSymbol {
-      match s {
+impl<'a> TryFrom<&'a str> for SurfaceSymbol {
+  type Error = UnknownToken;
+  fn try_from(s: &'a str) -> Result<SurfaceSymbol, UnknownToken> {
+      Ok(match s {
           "Aux" => Aux,
           "Never" => Never,
           "Not" => Not,
@@ -134,8 +148,35 @@ impl<'a> From<&'a str> for SurfaceSymbol {
 
           "P" => P_ { wa: FeatureVal::False },
           "P[+WA]" => P_ { wa: FeatureVal::True },
-          _ => panic!(format!("Illegal surface form: {}", s))
+          _ => return Err(UnknownToken(s.to_string()))
+      })
+  }
+}
+
+impl fmt::Display for SurfaceSymbol {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      let (name, wa, wh) = match self {
+          &Aux => return write!(f, "Aux"),
+          &Never => return write!(f, "Never"),
+          &Not => return write!(f, "Not"),
+          &Verb => return write!(f, "Verb"),
+          &Ka => return write!(f, "ka"),
+          &Adv_ { ref wa, ref wh } => ("Adv", wa, wh),
+          &O1_ { ref wa, ref wh } => ("O1", wa, wh),
+          &O2_ { ref wa, ref wh } => ("O2", wa, wh),
+          &O3_ { ref wa, ref wh } => ("O3", wa, wh),
+          &S_ { ref wa, ref wh } => ("S", wa, wh),
+          &P_ { ref wa } => ("P", wa, &FeatureVal::False)
+      };
+      // `==` matches Any, so test for the features the other way round
+      write!(f, "{}", name)?;
+      if !(wh == &FeatureVal::False) {
+          write!(f, "[+WH]")?;
+      }
+      if !(wa == &FeatureVal::False) {
+          write!(f, "[+WA]")?;
       }
+      Ok(())
   }
 }
 
@@ -146,30 +187,71 @@ pub enum Illoc {
   Imp
 }
 
-impl<'a> From <&'a str> for Illoc {
-  fn from(s: &'a str) -> Illoc {
+impl<'a> TryFrom<&'a str> for Illoc {
+  type Error = UnknownToken;
+  fn try_from(s: &'a str) -> Result<Illoc, UnknownToken> {
       match s {
-          "Q" => Illoc::Q,
-          "DEC" => Illoc::Dec,
-          "IMP" => Illoc::Imp,
-          _ => panic!(format!("Illegal illoc: {}", s))
+          "Q" => Ok(Illoc::Q),
+          "DEC" => Ok(Illoc::Dec),
+          "IMP" => Ok(Illoc::Imp),
+          _ => Err(UnknownToken(s.to_string()))
       }
   }
 }
 
+impl fmt::Display for Illoc {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      write!(f, "{}", match *self {
+          Illoc::Dec => "DEC",
+          Illoc::Q => "Q",
+          Illoc::Imp => "IMP"
+      })
+  }
+}
+
 #[derive(PartialEq, Debug)]
 pub struct SurfaceForm {pub illoc: Illoc, pub words: Vec<SurfaceSymbol> }
 
-impl<'a> From<&'a str> for SurfaceForm {
-  fn from(s: &'a str) -> Self {
-      let v: Vec<SurfaceSymbol> = s.split(" ")
-          .map({|s| s.into()})
-          .collect();
-      SurfaceForm {illoc: Illoc::Dec, words: v}
+impl<'a> TryFrom<&'a str> for SurfaceForm {
+  type Error = UnknownToken;
+  fn try_from(s: &'a str) -> Result<Self, UnknownToken> {
+      let v: Vec<SurfaceSymbol> = s.split_whitespace()
+          .map(SurfaceSymbol::try_from)
+          .collect::<Result<_, _>>()?;
+      Ok(SurfaceForm {illoc: Illoc::Dec, words: v})
+  }
+}
+
+impl fmt::Display for SurfaceForm {
+  /// Writes the illocution then the words, like `Q S[+WH] Aux Verb`.
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      write!(f, "{}", self.illoc)?;
+      for word in self.words.iter() {
+          write!(f, " {}", word)?;
+      }
+      Ok(())
   }
 }
 
 impl SurfaceForm {
+  /// Parses a sentence the way it's displayed, like `Q S[+WH] Aux Verb`. The
+  /// illocution can be left off for declaratives.
+  pub fn parse_with_illoc(s: &str) -> Result<Self, UnknownToken> {
+      let s = s.trim();
+      let (first, rest) = match s.find(' ') {
+          Some(n) => (&s[..n], s[n..].trim()),
+          None => (s, "")
+      };
+      match Illoc::try_from(first) {
+          Ok(illoc) => {
+              let mut form = SurfaceForm::try_from(rest)?;
+              form.illoc = illoc;
+              Ok(form)
+          },
+          Err(_) => SurfaceForm::try_from(s)
+      }
+  }
+
   pub fn contains(&self, sym: &SurfaceSymbol) -> bool {
       return self.words.contains(sym);
   }
@@ -276,12 +358,14 @@ impl SurfaceForm {
     // }
 }
 
+#[cfg(test)]
 mod bench {
     extern crate test;
     use self::test::Bencher;
 
     use sentence::{SurfaceSymbol::*, SurfaceForm, FeatureVal::*, FeatureType::*};
     use sentence::*;
+    use std::convert::TryFrom;
 
 
     #[bench]
@@ -289,7 +373,7 @@ mod bench {
         let x = SurfaceForm {illoc: Illoc::Dec, words: vec![Aux, O1_ {wh: True, wa: False}]};
         // b.iter(|| assert!(x.contains_feature(&WH)));
         let string =  "Aux Never Never Never O2[+WH][+WA] O1[+WH]";
-        let mut s: SurfaceForm = string.into();
+        let s = SurfaceForm::try_from(string).unwrap();
         b.iter(|| assert!(s.contains(&O2)));
     }
 
@@ -298,7 +382,7 @@ mod bench {
         let x = SurfaceForm {illoc: Illoc::Dec, words: vec![Aux, O1_ {wh: True, wa: False}]};
         // b.iter(|| assert!(x.contains_feature(&WH)));
         let string =  "Aux Never Never Never O2[+WH][+WA] O1[+WH]";
-        let mut s: SurfaceForm = string.into();
+        let s = SurfaceForm::try_from(string).unwrap();
         b.iter(|| assert!(!s.out_oblique()))
     }
     #[bench]
@@ -306,7 +390,77 @@ mod bench {
         let x = SurfaceForm {illoc: Illoc::Dec, words: vec![Aux, O1_ {wh: True, wa: False}]};
         // b.iter(|| assert!(x.contains_feature(&WH)));
         let string =  "P O2[+WH][+WA] O3 O1[+WH]";
-        let mut s: SurfaceForm = string.into();
+        let s = SurfaceForm::try_from(string).unwrap();
         b.iter(|| assert!(s.out_oblique()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::convert::TryFrom;
+    use domain::{Colag, LanguageDomain};
+    use sentence::{SurfaceForm, SurfaceSymbol, Illoc};
+
+    // Every word COLAG uses, spelled the way the sentences file spells it.
+    static WORDS: [&'static str; 27] = [
+        "Aux", "Never", "Not", "Verb", "ka", "P", "P[+WA]",
+        "Adv", "Adv[+WA]", "Adv[+WH]", "Adv[+WH][+WA]",
+        "O1", "O1[+WA]", "O1[+WH]", "O1[+WH][+WA]",
+        "O2", "O2[+WA]", "O2[+WH]", "O2[+WH][+WA]",
+        "O3", "O3[+WA]", "O3[+WH]", "O3[+WH][+WA]",
+        "S", "S[+WA]", "S[+WH]", "S[+WH][+WA]"
+    ];
+
+    #[test]
+    fn every_word_prints_back() {
+        for word in WORDS.iter() {
+            assert_eq!(SurfaceSymbol::try_from(*word).unwrap().to_string(), *word);
+        }
+        for illoc in ["DEC", "Q", "IMP"].iter() {
+            assert_eq!(Illoc::try_from(*illoc).unwrap().to_string(), *illoc);
+        }
+    }
+
+    #[test]
+    fn every_surface_form_prints_back() {
+        let lines = [
+            "DEC S Verb",
+            "DEC Adv Aux Never Verb O1 O2",
+            "Q S[+WH] Aux Verb",
+            "Q O1[+WH][+WA] ka Verb S[+WA]",
+            "IMP Verb P[+WA] O3 O2[+WH]",
+            "DEC Adv[+WH][+WA] Not Verb O1[+WA] O2[+WA] P O3[+WA]",
+            "IMP Verb",
+        ];
+        for line in lines.iter() {
+            assert_eq!(SurfaceForm::parse_with_illoc(line).unwrap().to_string(), *line);
+        }
+        // every word in every position, without which `by_surface_form`
+        // couldn't find a sentence by the form it was read with
+        let all = format!("Q {}", WORDS.join(" "));
+        assert_eq!(SurfaceForm::parse_with_illoc(&all).unwrap().to_string(), all);
+        // declaratives can leave the illocution off
+        assert_eq!(SurfaceForm::parse_with_illoc("S Verb O1").unwrap().to_string(),
+                   "DEC S Verb O1");
+        assert!(SurfaceForm::parse_with_illoc("Q S Verb[+WH]").is_err());
+    }
+
+    #[test]
+    fn sentences_are_found_by_the_form_they_were_read_with() {
+        // laid out like COLAG_2011_sents.txt, padding and all
+        let ids = "0\t0\t0\n0\t1\t1\n0\t2\t2\n";
+        let sents = "0\tDEC\tAdv Aux Never Verb        \n\
+                     1\tQ\tO1[+WH] Aux S Verb \n\
+                     2\tIMP\tVerb O1[+WA] P O3\n";
+        let domain = Colag::from_reader(Cursor::new(ids), "ids", 1).unwrap()
+            .read_surface_forms_from(Cursor::new(sents), "sents").unwrap();
+        for line in sents.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let expected = format!("{} {}", fields[1], fields[2].trim());
+            let sentence = fields[0].parse().unwrap();
+            assert_eq!(domain.lookup(&expected).unwrap().0, sentence);
+            assert_eq!(domain.surface_form(&sentence).unwrap().to_string(), expected);
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write, BufReader, BufWriter};
+use std::convert::TryFrom;
+use std::collections::{HashMap, HashSet};
+use std::time::UNIX_EPOCH;
+
+use domain::{Colag, LanguageDomain, Grammar, Sentence, Trigger, TriggerVec};
+use sentence::SurfaceForm;
+use tree::{Tree, TreeId};
+use triggers::TriggerMap;
+
+// A fully loaded `Colag` in a compact binary file, so that later runs can skip
+// parsing the text data files. The snapshot records the size and modification
+// time of every file it was built from and is only used while they match.
+//
+// Everything is little endian. After the magic number and version come the
+// sources, then the languages, named trigger tables, surface forms and
+// derivations, each as a count followed by that many entries. Surface forms
+// and derivations are stored as the strings they display as.
+const MAGIC: &'static [u8; 8] = b"COLAGSNP";
+const VERSION: u32 = 2;
+const NO_TREE: TreeId = ::std::u32::MAX;
+
+/// Returns the domain in the snapshot at `path` if it was built from
+/// `sources` as they are now, or None if it's missing, stale or unreadable.
+pub fn read(path: &str, sources: &[&str]) -> Option<Colag> {
+    let file = File::open(path).ok()?;
+    let mut r = BufReader::new(file);
+    let mut magic = [0; 8];
+    r.read_exact(&mut magic).ok()?;
+    if &magic != MAGIC || read_u32(&mut r).ok()? != VERSION {
+        return None;
+    }
+    let recorded = read_u32(&mut r).ok()? as usize;
+    if recorded != sources.len() {
+        return None;
+    }
+    for source in sources.iter() {
+        let (name, len, mtime) = (read_string(&mut r).ok()?, read_u64(&mut r).ok()?,
+                                  read_u64(&mut r).ok()?);
+        if name != *source || fingerprint(source).ok()? != (len, mtime) {
+            return None;
+        }
+    }
+    let domain = read_domain(&mut r).ok()?;
+    // anything after the last section means this isn't a file we wrote
+    if r.read(&mut [0]).ok()? != 0 {
+        return None;
+    }
+    Some(domain)
+}
+
+/// Writes `domain`, built from `sources`, to a snapshot at `path`. The
+/// snapshot is written alongside and renamed into place, so concurrent
+/// readers never see half of one.
+pub fn write(path: &str, sources: &[&str], domain: &Colag) -> io::Result<()> {
+    let partial = format!("{}.{}.partial", path, ::std::process::id());
+    {
+        let mut w = BufWriter::new(File::create(&partial)?);
+        w.write_all(MAGIC)?;
+        write_u32(&mut w, VERSION)?;
+        write_u32(&mut w, sources.len() as u32)?;
+        for source in sources.iter() {
+            let (len, mtime) = fingerprint(source)?;
+            write_string(&mut w, source)?;
+            write_u64(&mut w, len)?;
+            write_u64(&mut w, mtime)?;
+        }
+        write_domain(&mut w, domain)?;
+        w.flush()?;
+    }
+    fs::rename(&partial, path)
+}
+
+// The size and modification time in nanoseconds of `filename`.
+fn fingerprint(filename: &str) -> io::Result<(u64, u64)> {
+    let metadata = fs::metadata(filename)?;
+    let mtime = metadata.modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    Ok((metadata.len(), mtime.as_secs() * 1_000_000_000 + mtime.subsec_nanos() as u64))
+}
+
+fn write_domain<W: Write>(w: &mut W, domain: &Colag) -> io::Result<()> {
+    write_u32(w, domain.num_params() as u32)?;
+    write_u32(w, domain.grammars().len() as u32)?;
+    for grammar in domain.grammars().iter() {
+        let sentences = domain.language_vec(grammar).unwrap();
+        write_u32(w, grammar.0)?;
+        write_u32(w, sentences.len() as u32)?;
+        for sentence in sentences.iter() {
+            write_u32(w, *sentence)?;
+            write_u32(w, domain.tree(grammar, sentence).unwrap_or(NO_TREE))?;
+        }
+    }
+
+    let tables = domain.trigger_tables();
+    write_u32(w, tables.len() as u32)?;
+    for name in tables {
+        let table = domain.trigger_table(name).unwrap();
+        write_string(w, name)?;
+        write_u32(w, table.len() as u32)?;
+        for (sentence, trigger_vec) in table.iter() {
+            write_u32(w, *sentence)?;
+            let symbols: String = trigger_vec.iter().map(|t| t.symbol()).collect();
+            w.write_all(symbols.as_bytes())?;
+        }
+    }
+
+    let mut forms: Vec<&Sentence> = domain.surface_forms().collect();
+    forms.sort();
+    write_u32(w, forms.len() as u32)?;
+    for sentence in forms {
+        write_u32(w, *sentence)?;
+        write_string(w, &domain.surface_form(sentence).unwrap().to_string())?;
+    }
+
+    write_u32(w, domain.derivations().len() as u32)?;
+    for (id, derivation) in domain.derivations().iter() {
+        write_u32(w, *id)?;
+        write_string(w, &derivation.to_string())?;
+    }
+    Ok(())
+}
+
+fn read_domain<R: Read>(r: &mut R) -> io::Result<Colag> {
+    let num_params = read_u32(r)? as usize;
+    let mut language: HashMap<Grammar, HashSet<Sentence>> = HashMap::new();
+    let mut trees = Vec::new();
+    for _ in 0..read_u32(r)? {
+        let grammar = Grammar(read_u32(r)?);
+        let mut sentences = HashSet::new();
+        for _ in 0..read_u32(r)? {
+            let sentence = read_u32(r)?;
+            let tree = read_u32(r)?;
+            sentences.insert(sentence);
+            if tree != NO_TREE {
+                trees.push((grammar, sentence, tree));
+            }
+        }
+        language.insert(grammar, sentences);
+    }
+    let mut domain = Colag::from_languages(num_params, language);
+    for (grammar, sentence, tree) in trees {
+        domain.add_tree(grammar, sentence, tree);
+    }
+
+    let mut symbols = vec![0; num_params];
+    for _ in 0..read_u32(r)? {
+        let name = read_string(r)?;
+        let mut table = TriggerMap::new();
+        for _ in 0..read_u32(r)? {
+            let sentence = read_u32(r)?;
+            r.read_exact(&mut symbols)?;
+            let trigger_vec = symbols.iter()
+                .map(|&c| Trigger::try_from(c as char))
+                .collect::<Result<TriggerVec, _>>()
+                .map_err(|t| invalid(t.to_string()))?;
+            table.insert(sentence, trigger_vec);
+        }
+        domain.add_trigger_table(&name, table);
+    }
+
+    for _ in 0..read_u32(r)? {
+        let sentence = read_u32(r)?;
+        let form = SurfaceForm::parse_with_illoc(&read_string(r)?)
+            .map_err(|t| invalid(t.to_string()))?;
+        domain.add_surface_form(sentence, form);
+    }
+
+    for _ in 0..read_u32(r)? {
+        let id = read_u32(r)?;
+        let derivation = Tree::try_from(read_string(r)?.as_str())
+            .map_err(|t| invalid(t.to_string()))?;
+        domain.add_derivation(id, derivation);
+    }
+    Ok(domain)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_u32<W: Write>(w: &mut W, n: u32) -> io::Result<()> {
+    w.write_all(&n.to_le_bytes())
+}
+
+fn write_u64<W: Write>(w: &mut W, n: u64) -> io::Result<()> {
+    w.write_all(&n.to_le_bytes())
+}
+
+fn write_string<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    write_u32(w, s.len() as u32)?;
+    w.write_all(s.as_bytes())
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_string<R: Read>(r: &mut R) -> io::Result<String> {
+    let mut bytes = vec![0; read_u32(r)? as usize];
+    r.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| invalid(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::convert::TryFrom;
+    use domain::{Colag, LanguageDomain, Grammar};
+    use tree::Tree;
+    use snapshot;
+    use toy;
+
+    // The Gibson & Wexler domain with a derivation for one of its sentences.
+    fn domain() -> Colag {
+        let mut domain = toy::gibson_wexler();
+        domain.add_tree(Grammar(4), 0, 7);
+        domain.add_derivation(7, Tree::try_from("(CP (Spec S) (C' (C Verb)))").unwrap());
+        domain
+    }
+
+    fn assert_same(a: &Colag, b: &Colag) {
+        assert_eq!(a.num_params(), b.num_params());
+        assert_eq!(a.grammars(), b.grammars());
+        for g in a.grammars().iter() {
+            assert_eq!(a.language(g).unwrap(), b.language(g).unwrap());
+            for s in a.language_vec(g).unwrap().iter() {
+                assert_eq!(a.tree(g, s).ok(), b.tree(g, s).ok());
+            }
+        }
+        assert_eq!(a.trigger_tables(), b.trigger_tables());
+        for name in a.trigger_tables() {
+            let (x, y) = (a.trigger_table(name).unwrap(), b.trigger_table(name).unwrap());
+            assert_eq!(x.len(), y.len());
+            for (sentence, triggers) in x.iter() {
+                assert_eq!(Some(triggers), y.sentence(sentence));
+            }
+        }
+        let mut forms: Vec<_> = a.surface_forms().collect();
+        forms.sort();
+        let mut others: Vec<_> = b.surface_forms().collect();
+        others.sort();
+        assert_eq!(forms, others);
+        for s in forms {
+            assert_eq!(a.surface_form(s).unwrap(), b.surface_form(s).unwrap());
+        }
+        assert_eq!(a.derivations(), b.derivations());
+    }
+
+    // Runs `test` with a fresh source file and snapshot path, and cleans up
+    // after it.
+    fn with_files<F: FnOnce(&str, &str)>(name: &str, test: F) {
+        let dir = env::temp_dir();
+        let source = dir.join(format!("snapshot-{}-{}.txt", name, ::std::process::id()));
+        let path = dir.join(format!("snapshot-{}-{}.snapshot", name, ::std::process::id()));
+        fs::write(&source, "0\t0\t0\n").unwrap();
+        test(source.to_str().unwrap(), path.to_str().unwrap());
+        fs::remove_file(&source).unwrap();
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn reads_back_what_it_wrote() {
+        with_files("round-trip", |source, path| {
+            let domain = domain();
+            assert!(!domain.trigger_tables().is_empty());
+            snapshot::write(path, &[source], &domain).unwrap();
+            let read = snapshot::read(path, &[source]).expect("snapshot wasn't read");
+            assert_same(&domain, &read);
+        });
+    }
+
+    #[test]
+    fn changed_sources_invalidate_it() {
+        with_files("stale", |source, path| {
+            snapshot::write(path, &[source], &domain()).unwrap();
+            assert!(snapshot::read(path, &[]).is_none());
+            assert!(snapshot::read(path, &[source, source]).is_none());
+            OpenOptions::new().append(true).open(source).unwrap().write_all(b"1\t1\t1\n").unwrap();
+            assert!(snapshot::read(path, &[source]).is_none());
+        });
+    }
+
+    #[test]
+    fn trailing_bytes_invalidate_it() {
+        with_files("trailing", |source, path| {
+            snapshot::write(path, &[source], &domain()).unwrap();
+            assert!(snapshot::read(path, &[source]).is_some());
+            OpenOptions::new().append(true).open(path).unwrap().write_all(&[0]).unwrap();
+            assert!(snapshot::read(path, &[source]).is_none());
+        });
+    }
+}
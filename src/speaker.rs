@@ -1,57 +1,1085 @@
-use rand;
-use rand::{Rng, SeedableRng};
-use mersenne_twister::MersenneTwister;
+use std::error::Error;
+use std::io::Read;
+use std::str::FromStr;
+use std::collections::HashMap;
 
-use domain::{Colag, LanguageDomain, Grammar, Sentence};
+use rand::Rng;
+
+use domain::{Colag, LanguageDomain, Grammar, Sentence, Trigger, TriggerVec, DomainError, open,
+             data_reader};
+use hypothesis::{Hypothesis, WeightedHypothesis, Theory, THRESHOLD};
+use sentence::{SurfaceForm, Illoc, FeatureType};
+use checkpoint;
+use checkpoint::{Checkpoint, ResumableRng};
+use triggers;
+
+// A source of input sentences for a learner. Speakers are iterators over the
+// sentences they produce; most never run out. A speaker's checkpoint holds
+// where it is in its output, not how it was built.
+pub trait Speaker<'a>: Iterator<Item = &'a Sentence> + Checkpoint {
+    /// The grammar the speaker speaks, if there is a single one.
+    fn target(&self) -> Option<Grammar>;
+    /// Every grammar the speaker speaks.
+    fn components(&self) -> Vec<Grammar> {
+        self.target().into_iter().collect()
+    }
+    /// Shows the speaker the learner's current theory before each sentence.
+    /// Only speakers that adapt to the learner care.
+    fn observe(&mut self, _theory: &Theory) {}
+}
+
+impl<'a, S: Speaker<'a> + ?Sized> Speaker<'a> for Box<S> {
+    fn target(&self) -> Option<Grammar> {
+        (**self).target()
+    }
+    fn components(&self) -> Vec<Grammar> {
+        (**self).components()
+    }
+    fn observe(&mut self, theory: &Theory) {
+        (**self).observe(theory)
+    }
+}
 
 pub struct UniformRandomSpeaker<'a> {
     domain: &'a Colag,
     language: Grammar,
     sentences: &'a Vec<Sentence>,
-    rng: MersenneTwister
+    rng: ResumableRng
 }
 
 impl<'a> UniformRandomSpeaker<'a> {
-    pub fn new(domain: &'a Colag, language: Grammar) -> Self {
-        let mut rng = rand::thread_rng();
-        UniformRandomSpeaker {
+    /// Fails if `language` isn't legal in `domain`.
+    pub fn new(domain: &'a Colag, language: Grammar) -> Result<Self, DomainError> {
+        Ok(UniformRandomSpeaker {
             domain: domain,
             language: language,
-            sentences: domain
-                .language_vec(&language)
-                .expect(&format!("Illegal grammar: {}", language)),
-            rng: MersenneTwister::from_seed(rng.next_u64())
-        }
+            sentences: domain.language_vec(&language)?,
+            rng: checkpoint::new_rng()
+        })
     }
 }
 
 impl<'a> Iterator for UniformRandomSpeaker<'a> {
     type Item = &'a Sentence;
     fn next(&mut self) -> Option<Self::Item> {
-        Some(self.rng.choose(&self.sentences).unwrap())
+        self.rng.choose(&self.sentences)
     }
 }
 
+impl<'a> Speaker<'a> for UniformRandomSpeaker<'a> {
+    fn target(&self) -> Option<Grammar> {
+        Some(self.language)
+    }
+}
+
+impl<'a> Checkpoint for UniformRandomSpeaker<'a> {
+    fn save(&self) -> String {
+        self.rng.save()
+    }
+    fn restore(&mut self, state: &str) -> Result<(), String> {
+        self.rng = ResumableRng::restore(state)?;
+        Ok(())
+    }
+}
+
+// Draws indices in proportion to a list of weights.
+struct WeightedChoice {
+    cumulative: Vec<f64>
+}
+
+impl WeightedChoice {
+    /// Fails unless the weights are finite, none is negative and some are
+    /// positive.
+    fn new(weights: &[f64]) -> Result<Self, String> {
+        if let Some(w) = weights.iter().find(|w| !w.is_finite() || **w < 0.) {
+            return Err(format!("Bad weight: {}", w));
+        }
+        let mut total = 0.;
+        let choice = WeightedChoice {
+            cumulative: weights.iter().map(|w| { total += w; total }).collect()
+        };
+        if !(choice.total() > 0.) {
+            return Err("Nothing has any weight".to_string());
+        }
+        Ok(choice)
+    }
+
+    fn total(&self) -> f64 {
+        *self.cumulative.last().unwrap_or(&0.)
+    }
+
+    fn sample<R: Rng>(&self, rng: &mut R) -> usize {
+        let x = rng.next_f64() * self.total();
+        let n = self.cumulative.partition_point(|&c| c <= x);
+        n.min(self.cumulative.len() - 1)
+    }
+}
+
+// Speaks a language with Zipf-distributed sentence frequencies: the sentence
+// of rank r is heard in proportion to 1 / r^exponent. Ranks are a random
+// permutation of the language, since COLAG has no frequency data of its own.
+pub struct ZipfSpeaker<'a> {
+    language: Grammar,
+    sentences: Vec<&'a Sentence>,
+    choice: WeightedChoice,
+    rng: ResumableRng
+}
+
+impl<'a> ZipfSpeaker<'a> {
+    pub fn new(domain: &'a Colag, language: Grammar, exponent: f64) -> Result<Self, String> {
+        let mut rng = checkpoint::new_rng();
+        let mut sentences = sentences_of(domain, language)?;
+        rng.shuffle(&mut sentences);
+        let weights: Vec<f64> = (0..sentences.len())
+            .map(|rank| 1. / ((rank + 1) as f64).powf(exponent))
+            .collect();
+        Ok(ZipfSpeaker {
+            language: language,
+            sentences: sentences,
+            choice: WeightedChoice::new(&weights)?,
+            rng: rng
+        })
+    }
+}
+
+// The sentences of `language`, or an error if it's illegal or has none.
+fn sentences_of(domain: &Colag, language: Grammar) -> Result<Vec<&Sentence>, String> {
+    let sentences: Vec<&Sentence> = domain.language_vec(&language)
+        .map_err(|e| e.to_string())?
+        .iter()
+        .collect();
+    if sentences.is_empty() {
+        return Err(format!("The language of {} is empty", language));
+    }
+    Ok(sentences)
+}
+
+impl<'a> Iterator for ZipfSpeaker<'a> {
+    type Item = &'a Sentence;
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.sentences[self.choice.sample(&mut self.rng)])
+    }
+}
+
+impl<'a> Speaker<'a> for ZipfSpeaker<'a> {
+    fn target(&self) -> Option<Grammar> {
+        Some(self.language)
+    }
+}
+
+impl<'a> Checkpoint for ZipfSpeaker<'a> {
+    fn save(&self) -> String {
+        self.rng.save()
+    }
+    fn restore(&mut self, state: &str) -> Result<(), String> {
+        self.rng = ResumableRng::restore(state)?;
+        Ok(())
+    }
+}
+
+// Speaks a language with sentence frequencies read from a file of
+// space-separated `sentence weight` lines. Sentences of the language that
+// aren't in the file are never spoken, and sentences in the file that aren't
+// in the language are ignored.
+pub struct EmpiricalSpeaker<'a> {
+    language: Grammar,
+    sentences: Vec<&'a Sentence>,
+    choice: WeightedChoice,
+    rng: ResumableRng
+}
+
+impl<'a> EmpiricalSpeaker<'a> {
+    pub fn from_file(domain: &'a Colag, language: Grammar, filename: &str)
+                     -> Result<Self, Box<dyn Error>> {
+        EmpiricalSpeaker::from_reader(domain, language, open(filename)?, filename)
+    }
+
+    /// Reads `sentence frequency` lines from `source`, which may be
+    /// compressed. `name` identifies it in errors.
+    pub fn from_reader<R: Read>(domain: &'a Colag, language: Grammar, source: R,
+                                name: &str) -> Result<Self, Box<dyn Error>> {
+        let mut rdr = data_reader(source, b' ', name)?;
+        let mut frequencies: HashMap<Sentence, f64> = HashMap::new();
+        for result in rdr.deserialize() {
+            let (sentence, weight): (Sentence, f64) = result.map_err(|e| DomainError::csv(name, e))?;
+            *frequencies.entry(sentence).or_insert(0.) += weight;
+        }
+
+        let mut sentences = Vec::new();
+        let mut weights = Vec::new();
+        let language_vec = domain.language_vec(&language)
+            .map_err(|_| format!("Illegal grammar: {}", language))?;
+        for sentence in language_vec.iter() {
+            if let Some(&weight) = frequencies.get(sentence) {
+                sentences.push(sentence);
+                weights.push(weight);
+            }
+        }
+        let choice = WeightedChoice::new(&weights)
+            .map_err(|e| format!("{}, for the sentences of {} in {}", e, language, name))?;
+        Ok(EmpiricalSpeaker {
+            language: language,
+            sentences: sentences,
+            choice: choice,
+            rng: checkpoint::new_rng()
+        })
+    }
+}
+
+impl<'a> Iterator for EmpiricalSpeaker<'a> {
+    type Item = &'a Sentence;
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.sentences[self.choice.sample(&mut self.rng)])
+    }
+}
+
+impl<'a> Speaker<'a> for EmpiricalSpeaker<'a> {
+    fn target(&self) -> Option<Grammar> {
+        Some(self.language)
+    }
+}
+
+impl<'a> Checkpoint for EmpiricalSpeaker<'a> {
+    fn save(&self) -> String {
+        self.rng.save()
+    }
+    fn restore(&mut self, state: &str) -> Result<(), String> {
+        self.rng = ResumableRng::restore(state)?;
+        Ok(())
+    }
+}
+
+// Samples a language without replacement: every sentence is spoken once per
+// epoch, in a fresh random order each epoch.
+pub struct ShuffledSpeaker<'a> {
+    language: Grammar,
+    sentences: Vec<&'a Sentence>,
+    position: usize,
+    rng: ResumableRng
+}
+
+impl<'a> ShuffledSpeaker<'a> {
+    pub fn new(domain: &'a Colag, language: Grammar) -> Result<Self, String> {
+        let sentences = sentences_of(domain, language)?;
+        Ok(ShuffledSpeaker {
+            language: language,
+            position: sentences.len(),
+            sentences: sentences,
+            rng: checkpoint::new_rng()
+        })
+    }
+}
+
+impl<'a> Iterator for ShuffledSpeaker<'a> {
+    type Item = &'a Sentence;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position == self.sentences.len() {
+            self.rng.shuffle(&mut self.sentences);
+            self.position = 0;
+        }
+        self.position += 1;
+        Some(self.sentences[self.position - 1])
+    }
+}
+
+impl<'a> Speaker<'a> for ShuffledSpeaker<'a> {
+    fn target(&self) -> Option<Grammar> {
+        Some(self.language)
+    }
+}
+
+// position; the current order of the sentences; rng
+impl<'a> Checkpoint for ShuffledSpeaker<'a> {
+    fn save(&self) -> String {
+        let order: Vec<Sentence> = self.sentences.iter().map(|s| **s).collect();
+        checkpoint::join(&[self.position.to_string(), checkpoint::save_list(&order),
+                           self.rng.save()])
+    }
+    fn restore(&mut self, state: &str) -> Result<(), String> {
+        let fields = checkpoint::split(state, 3)?;
+        let position: usize = fields[0].parse().map_err(|_| format!("Bad position: {}", fields[0]))?;
+        let by_id: HashMap<Sentence, &'a Sentence> = self.sentences.iter()
+            .map(|s| (**s, *s))
+            .collect();
+        let sentences = checkpoint::restore_list::<Sentence>(fields[1])?.iter()
+            .map(|s| by_id.get(s).cloned()
+                 .ok_or_else(|| format!("Sentence {} isn't in {}", s, self.language)))
+            .collect::<Result<Vec<&'a Sentence>, _>>()?;
+        if sentences.len() != self.sentences.len() || position > sentences.len() {
+            return Err(format!("Not an order of {}: {}", self.language, state));
+        }
+        self.position = position;
+        self.sentences = sentences;
+        self.rng = ResumableRng::restore(fields[2])?;
+        Ok(())
+    }
+}
+
+// Replays a fixed sequence of sentences, then stops.
+pub struct ReplaySpeaker<'a> {
+    language: Option<Grammar>,
+    sentences: ::std::slice::Iter<'a, Sentence>
+}
+
+impl<'a> ReplaySpeaker<'a> {
+    pub fn new(sentences: &'a [Sentence], language: Option<Grammar>) -> Self {
+        ReplaySpeaker { language: language, sentences: sentences.iter() }
+    }
+}
+
+/// Reads a sentence sequence for a `ReplaySpeaker`, one sentence id a line.
+pub fn read_sentences(filename: &str) -> Result<Vec<Sentence>, Box<dyn Error>> {
+    read_sentences_from(open(filename)?, filename)
+}
+
+/// Reads a sentence sequence from `source`, which may be compressed. `name`
+/// identifies it in errors.
+pub fn read_sentences_from<R: Read>(source: R, name: &str)
+                                   -> Result<Vec<Sentence>, Box<dyn Error>> {
+    let mut rdr = data_reader(source, b',', name)?;
+    let mut sentences = Vec::new();
+    for result in rdr.deserialize() {
+        let sentence: Sentence = result.map_err(|e| DomainError::csv(name, e))?;
+        sentences.push(sentence);
+    }
+    Ok(sentences)
+}
+
+impl<'a> Iterator for ReplaySpeaker<'a> {
+    type Item = &'a Sentence;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.sentences.next()
+    }
+}
+
+impl<'a> Speaker<'a> for ReplaySpeaker<'a> {
+    fn target(&self) -> Option<Grammar> {
+        self.language
+    }
+}
+
+// the number of sentences left to replay
+impl<'a> Checkpoint for ReplaySpeaker<'a> {
+    fn save(&self) -> String {
+        self.sentences.len().to_string()
+    }
+    fn restore(&mut self, state: &str) -> Result<(), String> {
+        let left: usize = state.parse().map_err(|_| format!("Bad position: {}", state))?;
+        let len = self.sentences.len();
+        if left > len {
+            return Err(format!("Only {} sentences to replay, not {}", len, left));
+        }
+        if left < len {
+            self.sentences.nth(len - left - 1);
+        }
+        Ok(())
+    }
+}
+
+/// How many illegal grammars in a row to sample from a hypothesis before
+/// deciding it can't speak.
+pub const MAX_ATTEMPTS: usize = 10_000;
+
+// Speaks the languages of a weighted hypothesis, as a VL learner that has
+// become a teacher would: every utterance comes from a fresh grammar sampled
+// from the weights. It falls silent if the weights keep giving illegal
+// grammars (see MAX_ATTEMPTS).
+pub struct WeightedSpeaker<'a> {
+    domain: &'a Colag,
+    hypothesis: WeightedHypothesis,
+    rng: ResumableRng
+}
+
+impl<'a> WeightedSpeaker<'a> {
+    pub fn new(domain: &'a Colag, hypothesis: WeightedHypothesis) -> Self {
+        WeightedSpeaker {
+            domain: domain,
+            hypothesis: hypothesis,
+            rng: checkpoint::new_rng()
+        }
+    }
+}
+
+impl<'a> Iterator for WeightedSpeaker<'a> {
+    type Item = &'a Sentence;
+    fn next(&mut self) -> Option<Self::Item> {
+        for _ in 0..MAX_ATTEMPTS {
+            let grammar = Colag::random_weighted_grammar(&mut self.rng,
+                                                         &self.hypothesis.weights);
+            // illegal grammars can't speak, sample again.
+            if let Ok(sentences) = self.domain.language_vec(&grammar) {
+                return self.rng.choose(sentences);
+            }
+        }
+        None
+    }
+}
+
+// Where a NoisySpeaker's stray sentences come from.
+pub enum Noise {
+    // the language of a fixed second grammar
+    Grammar(Grammar),
+    // the language of a grammar picked at random for every stray sentence
+    RandomGrammar,
+    // any sentence of any language in the domain
+    Pool
+}
+
+// Wraps another speaker, replacing its sentence with one from `noise` with
+// probability `rate`.
+pub struct NoisySpeaker<'a, S: Speaker<'a>> {
+    domain: &'a Colag,
+    speaker: S,
+    noise: Noise,
+    rate: f64,
+    rng: ResumableRng
+}
+
+impl<'a, S: Speaker<'a>> NoisySpeaker<'a, S> {
+    /// Fails if the noise is a grammar that isn't legal in `domain`.
+    pub fn new(domain: &'a Colag, speaker: S, noise: Noise,
+               rate: f64) -> Result<Self, DomainError> {
+        if let Noise::Grammar(g) = noise {
+            domain.language_vec(&g)?;
+        }
+        Ok(NoisySpeaker {
+            domain: domain,
+            speaker: speaker,
+            noise: noise,
+            rate: rate,
+            rng: checkpoint::new_rng()
+        })
+    }
+}
+
+impl<'a, S: Speaker<'a>> Iterator for NoisySpeaker<'a, S> {
+    type Item = &'a Sentence;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rng.next_f64() >= self.rate {
+            return self.speaker.next();
+        }
+        match self.noise {
+            Noise::Grammar(g) => self.rng.choose(self.domain.language_vec(&g).unwrap()),
+            Noise::RandomGrammar => {
+                let g = self.domain.random_grammar(&mut self.rng);
+                self.rng.choose(self.domain.language_vec(g).unwrap())
+            },
+            Noise::Pool => self.rng.choose(self.domain.all_sentences())
+        }
+    }
+}
+
+impl<'a, S: Speaker<'a>> Speaker<'a> for NoisySpeaker<'a, S> {
+    fn target(&self) -> Option<Grammar> {
+        self.speaker.target()
+    }
+    fn observe(&mut self, theory: &Theory) {
+        self.speaker.observe(theory)
+    }
+    // with random grammar or pool noise, that's every grammar in the domain
+    fn components(&self) -> Vec<Grammar> {
+        let mut components = self.speaker.components();
+        let noise = match self.noise {
+            Noise::Grammar(g) => vec![g],
+            Noise::RandomGrammar | Noise::Pool => self.domain.grammars().clone()
+        };
+        for g in noise {
+            if !components.contains(&g) {
+                components.push(g);
+            }
+        }
+        components
+    }
+}
+
+// rng; the wrapped speaker
+impl<'a, S: Speaker<'a>> Checkpoint for NoisySpeaker<'a, S> {
+    fn save(&self) -> String {
+        checkpoint::join(&[self.rng.save(), self.speaker.save()])
+    }
+    fn restore(&mut self, state: &str) -> Result<(), String> {
+        let fields = checkpoint::split(state, 2)?;
+        self.rng = ResumableRng::restore(fields[0])?;
+        self.speaker.restore(fields[1])
+    }
+}
+
+// Speaks several languages at once: each sentence comes from one of
+// `grammars`, chosen in proportion to its weight.
+pub struct MixtureSpeaker<'a> {
+    grammars: Vec<Grammar>,
+    languages: Vec<&'a Vec<Sentence>>,
+    choice: WeightedChoice,
+    rng: ResumableRng
+}
+
+impl<'a> MixtureSpeaker<'a> {
+    /// Fails if a grammar is illegal or has an empty language, or the weights
+    /// aren't ones `WeightedChoice` can draw from.
+    pub fn new(domain: &'a Colag, grammars: &[(Grammar, f64)]) -> Result<Self, String> {
+        let weights: Vec<f64> = grammars.iter().map(|&(_, w)| w).collect();
+        let choice = WeightedChoice::new(&weights).map_err(|e| format!("{} in the mixture", e))?;
+        let mut languages = Vec::new();
+        for &(g, _) in grammars.iter() {
+            let language = domain.language_vec(&g).map_err(|e| e.to_string())?;
+            if language.is_empty() {
+                return Err(format!("The language of {} is empty", g));
+            }
+            languages.push(language);
+        }
+        Ok(MixtureSpeaker {
+            grammars: grammars.iter().map(|&(g, _)| g).collect(),
+            languages: languages,
+            choice: choice,
+            rng: checkpoint::new_rng()
+        })
+    }
+}
+
+impl<'a> Iterator for MixtureSpeaker<'a> {
+    type Item = &'a Sentence;
+    fn next(&mut self) -> Option<Self::Item> {
+        let n = self.choice.sample(&mut self.rng);
+        self.rng.choose(self.languages[n])
+    }
+}
+
+impl<'a> Speaker<'a> for MixtureSpeaker<'a> {
+    fn target(&self) -> Option<Grammar> {
+        if self.grammars.len() == 1 {
+            Some(self.grammars[0])
+        } else {
+            None
+        }
+    }
+    fn components(&self) -> Vec<Grammar> {
+        self.grammars.clone()
+    }
+}
+
+impl<'a> Checkpoint for MixtureSpeaker<'a> {
+    fn save(&self) -> String {
+        self.rng.save()
+    }
+    fn restore(&mut self, state: &str) -> Result<(), String> {
+        self.rng = ResumableRng::restore(state)?;
+        Ok(())
+    }
+}
+
+// Restricts which sentences of a language a ScheduledSpeaker will say.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    // sentences of at most this many words
+    MaxWords(usize),
+    // declaratives only
+    Declarative,
+    // no +WH or +WA words
+    NoFeatures
+}
+
+impl Filter {
+    pub fn allows(&self, form: &SurfaceForm) -> bool {
+        match *self {
+            Filter::MaxWords(n) => form.words.len() <= n,
+            Filter::Declarative => form.illoc == Illoc::Dec,
+            Filter::NoFeatures => !form.contains_feature(&FeatureType::WH)
+                && !form.contains_feature(&FeatureType::WA)
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+    /// Parses `words=N`, `dec` or `plain`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dec" => Ok(Filter::Declarative),
+            "plain" => Ok(Filter::NoFeatures),
+            _ if s.starts_with("words=") => s["words=".len()..].parse()
+                .map(Filter::MaxWords)
+                .map_err(|_| format!("Illegal filter: {}", s)),
+            _ => Err(format!("Illegal filter: {}", s))
+        }
+    }
+}
+
+// "Starting small" (Elman 1993): speaks only the part of a language its
+// current filter allows, widening to the whole language as it goes. Each
+// `(until, filter)` stage of the schedule lasts until `until` sentences have
+// been spoken in total.
+pub struct ScheduledSpeaker<'a> {
+    language: Grammar,
+    stages: Vec<(usize, Vec<&'a Sentence>)>,
+    full: Vec<&'a Sentence>,
+    spoken: usize,
+    rng: ResumableRng
+}
+
+impl<'a> ScheduledSpeaker<'a> {
+    /// Fails if a filter allows none of the language, since a schedule like
+    /// that is almost certainly a mistake.
+    pub fn new(domain: &'a Colag, language: Grammar,
+               schedule: &[(usize, Filter)]) -> Result<Self, String> {
+        let full = sentences_of(domain, language)?;
+        let mut stages = Vec::new();
+        for &(until, ref filter) in schedule.iter() {
+            let mut allowed: Vec<&'a Sentence> = Vec::new();
+            for &sent in full.iter() {
+                let form = domain.surface_form(sent).map_err(|e| e.to_string())?;
+                if filter.allows(form) {
+                    allowed.push(sent);
+                }
+            }
+            if allowed.is_empty() {
+                return Err(format!("{:?} allows none of the language of {}", filter, language));
+            }
+            stages.push((until, allowed));
+        }
+        Ok(ScheduledSpeaker {
+            language: language,
+            stages: stages,
+            full: full,
+            spoken: 0,
+            rng: checkpoint::new_rng()
+        })
+    }
+}
+
+impl<'a> Iterator for ScheduledSpeaker<'a> {
+    type Item = &'a Sentence;
+    fn next(&mut self) -> Option<Self::Item> {
+        let spoken = self.spoken;
+        self.spoken += 1;
+        let sentences = self.stages.iter()
+            .find(|&&(until, _)| spoken < until)
+            .map_or(&self.full, |&(_, ref sentences)| sentences);
+        self.rng.choose(sentences).cloned()
+    }
+}
+
+impl<'a> Speaker<'a> for ScheduledSpeaker<'a> {
+    fn target(&self) -> Option<Grammar> {
+        Some(self.language)
+    }
+}
+
+// sentences spoken; rng
+impl<'a> Checkpoint for ScheduledSpeaker<'a> {
+    fn save(&self) -> String {
+        checkpoint::join(&[self.spoken.to_string(), self.rng.save()])
+    }
+    fn restore(&mut self, state: &str) -> Result<(), String> {
+        let fields = checkpoint::split(state, 2)?;
+        self.spoken = fields[0].parse().map_err(|_| format!("Bad count: {}", fields[0]))?;
+        self.rng = ResumableRng::restore(fields[1])?;
+        Ok(())
+    }
+}
+
+// How a TeacherSpeaker picks its next sentence.
+#[derive(Debug, Clone, Copy)]
+pub enum Criterion {
+    // the sentences that are unambiguous triggers for the most parameters the
+    // learner hasn't yet set to the target's value
+    Triggers,
+    // the sentences the learner's most likely grammar fails to parse
+    Failures
+}
+
+// An oracle that watches the learner and picks the most informative sentence
+// of the target language under `criterion`, choosing at random among ties.
+// When no sentence is informative it speaks uniformly at random. The
+// informative sentences are only worked out again when what they depend on
+// changes: the parameters still unset, or the learner's most likely grammar.
+pub struct TeacherSpeaker<'a> {
+    domain: &'a Colag,
+    language: Grammar,
+    sentences: &'a Vec<Sentence>,
+    // the triggers of each of `sentences`, if the criterion needs them
+    triggers: Vec<&'a TriggerVec>,
+    criterion: Criterion,
+    informative: Vec<&'a Sentence>,
+    // what `informative` was worked out for
+    unset: Option<Vec<usize>>,
+    guess: Option<Grammar>,
+    rng: ResumableRng
+}
+
+impl<'a> TeacherSpeaker<'a> {
+    /// Fails if `language` isn't legal or, for `Criterion::Triggers`, if the
+    /// domain has no triggers for some sentence of it.
+    pub fn new(domain: &'a Colag, language: Grammar,
+               criterion: Criterion) -> Result<Self, DomainError> {
+        let sentences = domain.language_vec(&language)?;
+        let triggers = match criterion {
+            Criterion::Triggers => {
+                let table = domain.trigger_table(triggers::NORMAL)?;
+                sentences.iter()
+                    .map(|s| table.sentence(s).ok_or(DomainError::NoTriggers(*s)))
+                    .collect::<Result<Vec<&TriggerVec>, DomainError>>()?
+            },
+            Criterion::Failures => Vec::new()
+        };
+        Ok(TeacherSpeaker {
+            domain: domain,
+            language: language,
+            sentences: sentences,
+            triggers: triggers,
+            criterion: criterion,
+            informative: Vec::new(),
+            unset: None,
+            guess: None,
+            rng: checkpoint::new_rng()
+        })
+    }
+
+    // The parameters the learner hasn't yet set to the target's value.
+    fn unset(&self, beliefs: &[f64]) -> Vec<usize> {
+        let num_params = self.domain.num_params();
+        (0..num_params)
+            .filter(|&p| {
+                let target = self.language.get(p, num_params) as u8 as f64;
+                (beliefs[p] - target).abs() > THRESHOLD
+            })
+            .collect()
+    }
+
+    fn most_triggers(&self, unset: &[usize]) -> Vec<&'a Sentence> {
+        let mut best = 1;
+        let mut informative = Vec::new();
+        for (sent, triggers) in self.sentences.iter().zip(self.triggers.iter()) {
+            let score = unset.iter()
+                .filter(|&&p| match triggers[p] {
+                    Trigger::On | Trigger::Off => true,
+                    _ => false
+                })
+                .count();
+            if score > best {
+                best = score;
+                informative.clear();
+            }
+            if score == best {
+                informative.push(sent);
+            }
+        }
+        informative
+    }
+
+    fn failures(&self, grammar: Grammar) -> Vec<&'a Sentence> {
+        self.sentences.iter()
+            .filter(|s| self.domain.parses(&grammar, s).ok() != Some(true))
+            .collect()
+    }
+}
+
+impl<'a> Iterator for TeacherSpeaker<'a> {
+    type Item = &'a Sentence;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.informative.is_empty() {
+            self.rng.choose(self.sentences)
+        } else {
+            self.rng.choose(&self.informative).cloned()
+        }
+    }
+}
+
+impl<'a> Speaker<'a> for TeacherSpeaker<'a> {
+    fn target(&self) -> Option<Grammar> {
+        Some(self.language)
+    }
+    fn observe(&mut self, theory: &Theory) {
+        match self.criterion {
+            Criterion::Triggers => {
+                let unset = self.unset(&theory.beliefs());
+                if self.unset.as_ref() != Some(&unset) {
+                    self.informative = self.most_triggers(&unset);
+                    self.unset = Some(unset);
+                }
+            },
+            Criterion::Failures => {
+                let guess = theory.weighted().most_likely();
+                if self.guess != Some(guess) {
+                    self.informative = self.failures(guess);
+                    self.guess = Some(guess);
+                }
+            }
+        }
+    }
+}
+
+// The informative sentences are worked out again when the teacher next
+// observes the learner, so only the rng is saved.
+impl<'a> Checkpoint for TeacherSpeaker<'a> {
+    fn save(&self) -> String {
+        self.rng.save()
+    }
+    fn restore(&mut self, state: &str) -> Result<(), String> {
+        self.rng = ResumableRng::restore(state)?;
+        Ok(())
+    }
+}
+
+impl<'a> Speaker<'a> for WeightedSpeaker<'a> {
+    fn target(&self) -> Option<Grammar> {
+        None
+    }
+}
+
+impl<'a> Checkpoint for WeightedSpeaker<'a> {
+    fn save(&self) -> String {
+        self.rng.save()
+    }
+    fn restore(&mut self, state: &str) -> Result<(), String> {
+        self.rng = ResumableRng::restore(state)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
 mod bench {
     extern crate test;
     use self::test::Bencher;
-    use rand::{Rng, thread_rng};
-    use domain::{Colag, LanguageDomain, Sentence, Grammar, NUM_PARAMS};
+    use domain::{Colag, Sentence, Grammar};
     use speaker::{UniformRandomSpeaker};
 
     #[bench]
     fn speaker_iter(b: &mut Bencher) {
-        let colag = Colag::default();
-        let mut speaker = UniformRandomSpeaker::new(&colag, 611);
+        let colag = match Colag::for_bench() {
+            Some(colag) => colag,
+            None => return
+        };
+        let mut speaker = UniformRandomSpeaker::new(&colag, Grammar(611)).unwrap();
         b.iter(|| speaker.next().unwrap());
     }
 
     #[bench]
     fn speaker_vec(b: &mut Bencher) {
-        let colag = Colag::default();
-        let mut speaker = UniformRandomSpeaker::new(&colag, 611);
+        let colag = match Colag::for_bench() {
+            Some(colag) => colag,
+            None => return
+        };
+        let speaker = UniformRandomSpeaker::new(&colag, Grammar(611)).unwrap();
         let mut sentences: Vec<&Sentence> = speaker.take(20_000_000).collect();
 
         b.iter(|| test::black_box(sentences.pop()));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::env;
+    use std::io::{Cursor, Write};
+    use std::collections::{HashMap, HashSet};
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use domain::{Colag, LanguageDomain, Grammar, Sentence, DomainError};
+    use hypothesis::WeightedHypothesis;
+    use checkpoint::Checkpoint;
+    use speaker::{Speaker, WeightedSpeaker, WeightedChoice, ShuffledSpeaker, ReplaySpeaker,
+                  ZipfSpeaker, EmpiricalSpeaker, MixtureSpeaker, NoisySpeaker, Noise,
+                  UniformRandomSpeaker, ScheduledSpeaker, Filter, TeacherSpeaker, Criterion,
+                  read_sentences_from};
+    use hypothesis::Theory;
+    use domain::Trigger;
+    use checkpoint;
+    use toy;
+
+    // Two parameters, with grammar 3 illegal.
+    fn small_domain() -> Colag {
+        let mut language: HashMap<Grammar, HashSet<Sentence>> = HashMap::new();
+        language.insert(Grammar(0), [0, 1].iter().cloned().collect());
+        language.insert(Grammar(1), [1, 2].iter().cloned().collect());
+        language.insert(Grammar(2), [2, 3].iter().cloned().collect());
+        Colag::from_languages(2, language)
+    }
+
+    #[test]
+    fn weighted_speaker_gives_up_on_illegal_grammars() {
+        let domain = small_domain();
+        let illegal = WeightedHypothesis { weights: vec![1., 1.] };
+        assert_eq!(WeightedSpeaker::new(&domain, illegal).next(), None);
+        let legal = WeightedHypothesis { weights: vec![1., 0.] };
+        let spoken: Vec<&Sentence> = WeightedSpeaker::new(&domain, legal).take(10).collect();
+        assert_eq!(spoken.len(), 10);
+        assert!(spoken.iter().all(|s| **s == 2 || **s == 3));
+    }
+
+    #[test]
+    fn empty_languages_and_weights_are_rejected() {
+        let mut language: HashMap<Grammar, HashSet<Sentence>> = HashMap::new();
+        language.insert(Grammar(0), HashSet::new());
+        let domain = Colag::from_languages(1, language);
+        assert!(ShuffledSpeaker::new(&domain, Grammar(0)).is_err());
+        assert!(ZipfSpeaker::new(&domain, Grammar(0), 1.).is_err());
+        assert!(ShuffledSpeaker::new(&domain, Grammar(1)).is_err());
+        assert!(WeightedChoice::new(&[]).is_err());
+        assert!(WeightedChoice::new(&[0., 0.]).is_err());
+        assert!(WeightedChoice::new(&[1., -1.]).is_err());
+        let choice = WeightedChoice::new(&[0., 2., 0.]).unwrap();
+        let mut rng = checkpoint::new_rng();
+        assert!((0..100).all(|_| choice.sample(&mut rng) == 1));
+    }
+
+    #[test]
+    fn shuffled_speaker_says_every_sentence_once_per_epoch() {
+        let domain = toy::gibson_wexler();
+        let language = domain.language_vec(&Grammar(1)).unwrap();
+        let mut speaker = ShuffledSpeaker::new(&domain, Grammar(1)).unwrap();
+        for epoch in 0..3 {
+            let mut heard: Vec<Sentence> = speaker.by_ref()
+                .take(language.len())
+                .cloned()
+                .collect();
+            heard.sort();
+            assert_eq!(&heard, language, "epoch {}", epoch);
+        }
+    }
+
+    #[test]
+    fn replay_speaker_stops_at_the_end() {
+        let sentences = [3, 1, 2];
+        let mut speaker = ReplaySpeaker::new(&sentences, None);
+        assert_eq!(speaker.next(), Some(&3));
+        let mut resumed = ReplaySpeaker::new(&sentences, None);
+        resumed.restore(&speaker.save()).unwrap();
+        assert_eq!(speaker.cloned().collect::<Vec<Sentence>>(), vec![1, 2]);
+        assert_eq!(resumed.cloned().collect::<Vec<Sentence>>(), vec![1, 2]);
+        let mut speaker = ReplaySpeaker::new(&sentences, None);
+        assert_eq!(speaker.by_ref().count(), 3);
+        assert_eq!(speaker.next(), None);
+    }
+
+    #[test]
+    fn zipf_speaker_favours_its_top_sentence() {
+        let domain = toy::gibson_wexler();
+        let language = domain.language(&Grammar(4)).unwrap();
+        let speaker = ZipfSpeaker::new(&domain, Grammar(4), 5.).unwrap();
+        let mut counts: HashMap<Sentence, usize> = HashMap::new();
+        for sent in speaker.take(1000) {
+            assert!(language.contains(sent));
+            *counts.entry(*sent).or_insert(0) += 1;
+        }
+        // 1 / (1 + 2^-5 + 3^-5 + ...) of the time, about 96%
+        assert!(*counts.values().max().unwrap() > 900);
+    }
+
+    #[test]
+    fn empirical_speaker_follows_its_file() {
+        let domain = toy::gibson_wexler();
+        let language = domain.language_vec(&Grammar(4)).unwrap();
+        // one sentence of the language weighted 3 to 1 over another, and one
+        // from outside it
+        let outside = *domain.language_vec(&Grammar(0)).unwrap().iter()
+            .find(|s| !language.contains(s))
+            .unwrap();
+        let path = env::temp_dir().join(format!("empirical-{}.txt", ::std::process::id()));
+        fs::write(&path, format!("{} 3\n{} 1\n{} 100\n", language[0], language[1], outside))
+            .unwrap();
+        let speaker = EmpiricalSpeaker::from_file(&domain, Grammar(4), path.to_str().unwrap())
+            .unwrap();
+        let heard: Vec<Sentence> = speaker.take(4000).cloned().collect();
+        fs::remove_file(&path).unwrap();
+        let first = heard.iter().filter(|s| **s == language[0]).count();
+        assert!(heard.iter().all(|s| *s == language[0] || *s == language[1]));
+        assert!(first > 2800 && first < 3200, "{} of 4000", first);
+    }
+
+    fn gzip(data: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn sentence_files_can_be_compressed() {
+        assert_eq!(read_sentences_from(Cursor::new(gzip("3\n1\n2\n")), "replay").unwrap(),
+                   vec![3, 1, 2]);
+        let error = read_sentences_from(Cursor::new("1\nx\n"), "replay").unwrap_err();
+        assert!(error.to_string().starts_with("replay:2:"), "{}", error);
+
+        let domain = toy::gibson_wexler();
+        let language = domain.language_vec(&Grammar(4)).unwrap();
+        let frequencies = gzip(&format!("{} 1\n", language[2]));
+        let speaker = EmpiricalSpeaker::from_reader(&domain, Grammar(4), Cursor::new(frequencies),
+                                                    "freqs").unwrap();
+        assert!(speaker.take(10).all(|s| *s == language[2]));
+    }
+
+    #[test]
+    fn mixture_weights_are_checked() {
+        let domain = toy::gibson_wexler();
+        assert!(MixtureSpeaker::new(&domain, &[]).is_err());
+        assert!(MixtureSpeaker::new(&domain, &[(Grammar(4), 0.), (Grammar(5), 0.)]).is_err());
+        assert!(MixtureSpeaker::new(&domain, &[(Grammar(4), 1.), (Grammar(5), -1.)]).is_err());
+        assert!(MixtureSpeaker::new(&domain, &[(Grammar(4), 1.), (Grammar(8), 1.)]).is_err());
+        let mixture = MixtureSpeaker::new(&domain, &[(Grammar(4), 1.), (Grammar(5), 0.)]).unwrap();
+        let language = domain.language(&Grammar(4)).unwrap();
+        assert!(mixture.take(100).all(|s| language.contains(s)));
+    }
+
+    #[test]
+    fn noisy_components_cover_the_noise() {
+        let domain = toy::gibson_wexler();
+        let target = || UniformRandomSpeaker::new(&domain, Grammar(4)).unwrap();
+        let noisy = |noise| NoisySpeaker::new(&domain, target(), noise, 0.1);
+        assert_eq!(noisy(Noise::Grammar(Grammar(5))).unwrap().components(),
+                   vec![Grammar(4), Grammar(5)]);
+        assert!(noisy(Noise::Grammar(Grammar(8))).is_err());
+        assert!(UniformRandomSpeaker::new(&domain, Grammar(8)).is_err());
+        for noise in vec![Noise::RandomGrammar, Noise::Pool] {
+            let mut components = noisy(noise).unwrap().components();
+            components.sort();
+            assert_eq!(&components, domain.grammars());
+        }
+    }
+
+    #[test]
+    fn schedules_must_allow_something() {
+        let domain = toy::gibson_wexler();
+        assert!(ScheduledSpeaker::new(&domain, Grammar(4), &[(10, Filter::MaxWords(1))]).is_err());
+        let speaker = ScheduledSpeaker::new(&domain, Grammar(4), &[(10, Filter::MaxWords(2))])
+            .unwrap();
+        let heard: Vec<&Sentence> = speaker.take(20).collect();
+        assert!(heard[..10].iter().all(|s| domain.surface_form(s).unwrap().words.len() <= 2));
+    }
+
+    #[test]
+    fn teacher_speaks_the_best_triggers_until_the_learner_is_set() {
+        let domain = toy::gibson_wexler();
+        let mut teacher = TeacherSpeaker::new(&domain, Grammar(4), Criterion::Triggers).unwrap();
+        let unambiguous = |s: &Sentence| domain.triggers(s).unwrap().iter()
+            .filter(|t| **t == Trigger::On || **t == Trigger::Off)
+            .count();
+        let language = domain.language_vec(&Grammar(4)).unwrap();
+        let best = language.iter().map(|s| unambiguous(s)).max().unwrap();
+        assert!(best > 0);
+        let unsure = WeightedHypothesis::new(3);
+        for _ in 0..20 {
+            teacher.observe(&Theory::Weighted(&unsure));
+            assert_eq!(unambiguous(teacher.next().unwrap()), best);
+        }
+        // with every parameter set there's nothing to teach, so anything goes
+        let sure = WeightedHypothesis::from_grammar(&Grammar(4), 3);
+        teacher.observe(&Theory::Weighted(&sure));
+        let mut heard: Vec<Sentence> = teacher.take(1000).cloned().collect();
+        heard.sort();
+        heard.dedup();
+        assert_eq!(&heard, language);
+    }
+
+    #[test]
+    fn teacher_needs_a_legal_target_and_triggers_to_teach_with() {
+        let domain = small_domain();
+        match TeacherSpeaker::new(&domain, Grammar(0), Criterion::Triggers) {
+            Err(DomainError::NoTriggerTable(_)) => (),
+            _ => panic!("expected no trigger table")
+        }
+        match TeacherSpeaker::new(&domain, Grammar(3), Criterion::Failures) {
+            Err(DomainError::IllegalGrammar(Grammar(3))) => (),
+            _ => panic!("expected an illegal grammar")
+        }
+        assert!(TeacherSpeaker::new(&domain, Grammar(0), Criterion::Failures).is_ok());
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use domain::{LanguageDomain, Grammar, DomainError};
+use learner::{Learner, Environment, TriggerLearner};
+use speaker::UniformRandomSpeaker;
+
+const TOLERANCE: f64 = 1e-12;
+pub const MAX_ITERATIONS: usize = 1_000_000;
+
+// Values worked out by iterating to a fixed point. `settled` is false if they
+// were still changing by more than TOLERANCE after MAX_ITERATIONS, in which
+// case they're only approximate.
+pub struct Solution<T> {
+    pub values: Vec<T>,
+    pub settled: bool
+}
+
+// The Triggering Learning Algorithm (Gibson & Wexler 1994) as an exact Markov
+// chain over the grammars of a domain. On each sentence the learner keeps its
+// grammar if it parses the sentence. Otherwise it flips one parameter chosen
+// uniformly at random and adopts the new grammar only if that one parses the
+// sentence (single value constraint + greediness). Input sentences are drawn
+// uniformly from the target language.
+pub struct TlaChain {
+    pub target: Grammar,
+    pub states: Vec<Grammar>,
+    // sparse off-diagonal rows of the transition matrix, indexed like `states`
+    transitions: Vec<Vec<(usize, f64)>>,
+    // probability of staying put, ie. the diagonal of the transition matrix
+    stay: Vec<f64>,
+    // true for states whose language is extensionally equal to the target's
+    converged: Vec<bool>,
+}
+
+impl TlaChain {
+    pub fn new<D: LanguageDomain>(domain: &D, target: Grammar) -> Result<TlaChain, DomainError> {
+        let target_sents = domain.language_vec(&target)?;
+        let target_lang = domain.language(&target)?;
+        let mut states = domain.grammars().clone();
+        states.sort();
+        let index: HashMap<Grammar, usize> = states.iter()
+            .enumerate()
+            .map(|(n, g)| (*g, n))
+            .collect();
+        let num_params = domain.num_params();
+        let step = 1. / (target_sents.len() * num_params) as f64;
+
+        let mut transitions = Vec::with_capacity(states.len());
+        let mut stay = Vec::with_capacity(states.len());
+        let mut converged = Vec::with_capacity(states.len());
+        for grammar in states.iter() {
+            let lang = domain.language(grammar)?;
+            let neighbours = domain.legal_neighbours(grammar);
+            let mut counts: HashMap<usize, usize> = HashMap::new();
+            for sent in target_sents.iter().filter(|s| !lang.contains(s)) {
+                for neighbour in neighbours.iter() {
+                    if domain.parses(neighbour, sent)? {
+                        *counts.entry(index[neighbour]).or_insert(0) += 1;
+                    }
+                }
+            }
+            let mut row: Vec<(usize, f64)> = counts.into_iter()
+                .map(|(state, count)| (state, count as f64 * step))
+                .collect();
+            row.sort_by_key(|&(state, _)| state);
+            stay.push(1. - row.iter().map(|&(_, p)| p).sum::<f64>());
+            transitions.push(row);
+            converged.push(lang == target_lang);
+        }
+
+        Ok(TlaChain { target, states, transitions, stay, converged })
+    }
+
+    /// Returns true if the learner can never leave state `n`.
+    pub fn absorbing(&self, n: usize) -> bool {
+        self.transitions[n].is_empty()
+    }
+
+    /// Returns true if state `n` generates the target language.
+    pub fn converged(&self, n: usize) -> bool {
+        self.converged[n]
+    }
+
+    /// Absorbing states that do not generate the target language.
+    pub fn local_maxima(&self) -> Vec<Grammar> {
+        (0..self.states.len())
+            .filter(|&n| self.absorbing(n) && !self.converged[n])
+            .map(|n| self.states[n])
+            .collect()
+    }
+
+    /// Probability of eventually reaching the target language from each state.
+    pub fn convergence_probabilities(&self) -> Solution<f64> {
+        let mut probs: Vec<f64> = self.converged.iter()
+            .map(|&c| if c { 1. } else { 0. })
+            .collect();
+        let transient: Vec<bool> = (0..self.states.len())
+            .map(|n| !self.converged[n] && !self.absorbing(n))
+            .collect();
+        let settled = self.solve(&mut probs, 0., &transient);
+        Solution { values: probs, settled }
+    }
+
+    /// Expected number of sentences before reaching the target language from
+    /// each state, or None if the learner might never get there.
+    pub fn expected_sentences(&self) -> Solution<Option<f64>> {
+        let probs = self.convergence_probabilities();
+        let certain: Vec<bool> = probs.values.iter().map(|&p| p > 1. - 1e-9).collect();
+        let transient: Vec<bool> = (0..self.states.len())
+            .map(|n| certain[n] && !self.converged[n])
+            .collect();
+        let mut times = vec![0.; self.states.len()];
+        let settled = self.solve(&mut times, 1., &transient) && probs.settled;
+        let values = times.into_iter()
+            .zip(certain)
+            .map(|(t, c)| if c { Some(t) } else { None })
+            .collect();
+        Solution { values, settled }
+    }
+
+    /// The share of `runs` runs of the TriggerLearner from `start` that reach
+    /// the target language, to check `convergence_probabilities` against. A
+    /// run is given up on at a local maximum or after `max_sentences`
+    /// sentences.
+    pub fn simulate(&self, env: &Environment, start: Grammar, runs: usize,
+                    max_sentences: usize) -> Result<f64, DomainError> {
+        let state = |g: Grammar| self.states.binary_search(&g)
+            .map_err(|_| DomainError::IllegalGrammar(g));
+        state(start)?;
+        let num_params = env.domain.num_params();
+        let mut converged = 0;
+        for _ in 0..runs {
+            let mut learner = TriggerLearner::starting_at(start, num_params);
+            let mut speaker = UniformRandomSpeaker::new(&env.domain, self.target)?;
+            for heard in 0.. {
+                let n = state(learner.guess())?;
+                if self.converged(n) {
+                    converged += 1;
+                    break;
+                }
+                if self.absorbing(n) || heard == max_sentences {
+                    break;
+                }
+                match speaker.next() {
+                    Some(sent) => learner.learn(env, sent),
+                    None => break
+                }
+            }
+        }
+        Ok(converged as f64 / runs as f64)
+    }
+
+    // Gauss-Seidel iteration of x = cost + P x over the `transient` states,
+    // with the self loop folded into the denominator. All other states keep the
+    // value they came in with. Returns false if the values hadn't settled
+    // after MAX_ITERATIONS.
+    fn solve(&self, values: &mut Vec<f64>, cost: f64, transient: &[bool]) -> bool {
+        for _ in 0..MAX_ITERATIONS {
+            let mut delta: f64 = 0.;
+            for n in 0..self.states.len() {
+                if !transient[n] {
+                    continue;
+                }
+                let mut total = cost;
+                for &(state, p) in self.transitions[n].iter() {
+                    total += p * values[state];
+                }
+                let value = total / (1. - self.stay[n]);
+                delta = delta.max((value - values[n]).abs() / value.max(1.));
+                values[n] = value;
+            }
+            if delta < TOLERANCE {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use checkpoint;
+    use domain::{LanguageDomain, Grammar};
+    use learner::Environment;
+    use tla::TlaChain;
+    use toy;
+
+    const RUNS: usize = 2000;
+    const MAX_SENTENCES: usize = 10_000;
+
+    #[test]
+    fn convergence_matches_the_trigger_learner() {
+        checkpoint::seed_thread(26);
+        let env = Environment { domain: toy::gibson_wexler() };
+        for &target in env.domain.grammars().iter() {
+            let chain = TlaChain::new(&env.domain, target).unwrap();
+            let probs = chain.convergence_probabilities();
+            assert!(probs.settled);
+            for (n, &start) in chain.states.iter().enumerate() {
+                let simulated = chain.simulate(&env, start, RUNS, MAX_SENTENCES).unwrap();
+                assert!((simulated - probs.values[n]).abs() < 0.05,
+                        "target {} from {}: exact {}, simulated {}",
+                        target, start, probs.values[n], simulated);
+            }
+        }
+        let chain = TlaChain::new(&env.domain, Grammar(0)).unwrap();
+        assert!(chain.simulate(&env, Grammar(8), 1, MAX_SENTENCES).is_err());
+    }
+
+    #[test]
+    fn expected_sentences_only_where_certain() {
+        let domain = toy::gibson_wexler();
+        let chain = TlaChain::new(&domain, Grammar(4)).unwrap();
+        let probs = chain.convergence_probabilities().values;
+        let times = chain.expected_sentences();
+        assert!(times.settled);
+        for n in 0..chain.states.len() {
+            assert_eq!(times.values[n].is_some(), probs[n] > 1. - 1e-9);
+            if chain.converged(n) {
+                assert_eq!(times.values[n], Some(0.));
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+
+use rand::Rng;
+
+use domain::{Colag, Grammar, Sentence};
+use sentence::{SurfaceForm};
+
+// The three parameter space of Gibson & Wexler (1994), as tabulated by Niyogi
+// & Berwick (1996). Parameters are, in order: Spec (1 = specifier first), Comp
+// (1 = complement first) and V2, so grammar 4 is SVO and grammar 7 is German
+// style SOV+V2. Every sentence is a degree-0 declarative.
+static GIBSON_WEXLER: [&'static [&'static str]; 8] = [
+    // 0 0 0: VOS
+    &["V S", "V O S", "V O1 O2 S", "Aux V S", "Aux V O S", "Aux V O1 O2 S",
+      "Adv V S", "Adv V O S", "Adv V O1 O2 S", "Adv Aux V S", "Adv Aux V O S",
+      "Adv Aux V O1 O2 S"],
+    // 0 0 1: VOS+V2
+    &["S V", "S V O", "O V S", "S V O1 O2", "O1 V O2 S", "O2 V O1 S", "S Aux V",
+      "S Aux V O", "O Aux V S", "S Aux V O1 O2", "O1 Aux V O2 S", "O2 Aux V O1 S",
+      "Adv V S", "Adv V O S", "Adv V O1 O2 S", "Adv Aux V S", "Adv Aux V O S",
+      "Adv Aux V O1 O2 S"],
+    // 0 1 0: OVS
+    &["V S", "O V S", "O2 O1 V S", "V Aux S", "O V Aux S", "O2 O1 V Aux S",
+      "Adv V S", "Adv O V S", "Adv O2 O1 V S", "Adv V Aux S", "Adv O V Aux S",
+      "Adv O2 O1 V Aux S"],
+    // 0 1 1: OVS+V2
+    &["S V", "O V S", "S V O", "S V O2 O1", "O1 V O2 S", "O2 V O1 S", "S Aux V",
+      "S Aux O V", "O Aux V S", "S Aux O2 O1 V", "O1 Aux O2 V S", "O2 Aux O1 V S",
+      "Adv V S", "Adv V O S", "Adv V O2 O1 S", "Adv Aux V S", "Adv Aux O V S",
+      "Adv Aux O2 O1 V S"],
+    // 1 0 0: SVO
+    &["S V", "S V O", "S V O1 O2", "S Aux V", "S Aux V O", "S Aux V O1 O2",
+      "Adv S V", "Adv S V O", "Adv S V O1 O2", "Adv S Aux V", "Adv S Aux V O",
+      "Adv S Aux V O1 O2"],
+    // 1 0 1: SVO+V2
+    &["S V", "S V O", "O V S", "S V O1 O2", "O1 V S O2", "O2 V S O1", "S Aux V",
+      "S Aux V O", "O Aux S V", "S Aux V O1 O2", "O1 Aux S V O2", "O2 Aux S V O1",
+      "Adv V S", "Adv V S O", "Adv V S O1 O2", "Adv Aux S V", "Adv Aux S V O",
+      "Adv Aux S V O1 O2"],
+    // 1 1 0: SOV
+    &["S V", "S O V", "S O2 O1 V", "S V Aux", "S O V Aux", "S O2 O1 V Aux",
+      "Adv S V", "Adv S O V", "Adv S O2 O1 V", "Adv S V Aux", "Adv S O V Aux",
+      "Adv S O2 O1 V Aux"],
+    // 1 1 1: SOV+V2
+    &["S V", "S V O", "O V S", "S V O2 O1", "O1 V S O2", "O2 V S O1", "S Aux V",
+      "S Aux O V", "O Aux S V", "S Aux O2 O1 V", "O1 Aux S O2 V", "O2 Aux S O1 V",
+      "Adv V S", "Adv V S O", "Adv V S O2 O1", "Adv Aux S V", "Adv Aux S O V",
+      "Adv Aux S O2 O1 V"],
+];
+
+/// The Gibson & Wexler three parameter domain, with triggers and surface
+/// forms. Sentences are numbered in order of first appearance in the table.
+pub fn gibson_wexler() -> Colag {
+    let mut ids: HashMap<&str, Sentence> = HashMap::new();
+    let mut forms: Vec<&str> = Vec::new();
+    let mut language: HashMap<Grammar, HashSet<Sentence>> = HashMap::new();
+    for (grammar, sentences) in GIBSON_WEXLER.iter().enumerate() {
+        let mut lang = HashSet::new();
+        for form in sentences.iter() {
+            let id = *ids.entry(*form).or_insert_with(|| {
+                forms.push(*form);
+                (forms.len() - 1) as Sentence
+            });
+            lang.insert(id);
+        }
+        language.insert(Grammar(grammar as u32), lang);
+    }
+
+    let mut domain = Colag::from_languages(3, language);
+    for (id, form) in forms.iter().enumerate() {
+        // spelled the way COLAG spells its words
+        let colag_form: Vec<&str> = form.split(' ')
+            .map(|w| match w {
+                "V" => "Verb",
+                "O" => "O1",
+                w => w
+            })
+            .collect();
+        let form = SurfaceForm::try_from(colag_form.join(" ").as_str()).unwrap();
+        domain.add_surface_form(id as Sentence, form);
+    }
+    domain.gen_triggers();
+    domain
+}
+
+/// The most parameters a synthetic domain can have. Generating one takes time
+/// in the square of its number of grammars, so this is COLAG's size.
+pub const MAX_SYNTHETIC_PARAMS: usize = 13;
+
+// Settings for a random domain. Every grammar of `num_params` parameters, from
+// 1 to MAX_SYNTHETIC_PARAMS, is legal and starts out with `sentences`
+// sentences of its own.
+pub struct Synthetic {
+    pub num_params: usize,
+    pub sentences: usize,
+    // chance that a sentence is also generated by any other given grammar
+    pub ambiguity: f64,
+    // chance that a grammar's language is folded into another grammar's,
+    // making it (almost always) a proper subset of that one
+    pub subsets: f64
+}
+
+/// Generates a random domain with triggers but no surface forms.
+pub fn synthetic<R: Rng>(rng: &mut R, settings: &Synthetic) -> Colag {
+    assert!(settings.num_params >= 1 && settings.num_params <= MAX_SYNTHETIC_PARAMS,
+            "synthetic domains have 1 to {} parameters", MAX_SYNTHETIC_PARAMS);
+    let num_grammars = 1 << settings.num_params;
+    let mut language: Vec<HashSet<Sentence>> = vec![HashSet::new(); num_grammars];
+    let mut next: Sentence = 0;
+    for grammar in 0..num_grammars {
+        for _ in 0..settings.sentences {
+            for other in 0..num_grammars {
+                if other == grammar || rng.next_f64() < settings.ambiguity {
+                    language[other].insert(next);
+                }
+            }
+            next += 1;
+        }
+    }
+    for grammar in 0..num_grammars {
+        if num_grammars > 1 && rng.next_f64() < settings.subsets {
+            let mut superset = grammar;
+            while superset == grammar {
+                superset = rng.gen_range(0, num_grammars);
+            }
+            let sentences = language[grammar].clone();
+            language[superset].extend(sentences);
+        }
+    }
+
+    let language = language.into_iter()
+        .enumerate()
+        .map(|(g, sentences)| (Grammar(g as u32), sentences))
+        .collect();
+    let mut domain = Colag::from_languages(settings.num_params, language);
+    domain.gen_triggers();
+    domain
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::{LanguageDomain, Grammar};
+    use tla::TlaChain;
+    use toy;
+
+    // The local maxima Gibson & Wexler found: every trap is a +V2 grammar
+    // under a -V2 target, and the +V2 targets have none.
+    #[test]
+    fn gibson_wexler_has_the_published_local_maxima() {
+        let domain = toy::gibson_wexler();
+        assert_eq!(domain.grammars().len(), 8);
+        let expected: [&[u32]; 8] = [&[], &[], &[5, 7], &[], &[1], &[], &[1, 3], &[]];
+        for (target, maxima) in expected.iter().enumerate() {
+            let chain = TlaChain::new(&domain, Grammar(target as u32)).unwrap();
+            let maxima: Vec<Grammar> = maxima.iter().cloned().map(Grammar).collect();
+            assert_eq!(chain.local_maxima(), maxima, "target {}", target);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::convert::TryFrom;
+
+use sentence::UnknownToken;
+
+/// Identifies a derivation in COLAG. Every (grammar, sentence) pair is listed
+/// with the id of the structure the grammar assigns the sentence, so pairs
+/// with the same id share a derivation.
+pub type TreeId = u32;
+
+// A derivation: a labelled node and its children, leaves having none.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tree {
+    pub label: String,
+    pub children: Vec<Tree>
+}
+
+impl Tree {
+    pub fn leaf(label: &str) -> Tree {
+        Tree { label: label.to_string(), children: Vec::new() }
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// The labels of the leaves, left to right.
+    pub fn leaves(&self) -> Vec<&str> {
+        if self.is_leaf() {
+            return vec![&self.label];
+        }
+        self.children.iter().flat_map(|c| c.leaves()).collect()
+    }
+
+    pub fn depth(&self) -> usize {
+        1 + self.children.iter().map(|c| c.depth()).max().unwrap_or(0)
+    }
+
+    // Parses one tree off the front of `tokens`.
+    fn parse<'a, I>(tokens: &mut Peekable<I>) -> Result<Tree, UnknownToken>
+        where I: Iterator<Item=&'a str>
+    {
+        let bad = |t: &str| UnknownToken(t.to_string());
+        match tokens.next() {
+            Some("(") => {
+                let mut tree = match tokens.next() {
+                    Some(t) if t != "(" && t != ")" => Tree::leaf(t),
+                    Some(t) => return Err(bad(t)),
+                    None => return Err(bad("("))
+                };
+                loop {
+                    match tokens.peek() {
+                        Some(&")") => {
+                            tokens.next();
+                            return Ok(tree);
+                        },
+                        Some(_) => tree.children.push(Tree::parse(tokens)?),
+                        None => return Err(bad("("))
+                    }
+                }
+            },
+            Some(t) if t == ")" => Err(bad(t)),
+            Some(t) => Ok(Tree::leaf(t)),
+            None => Err(bad(""))
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Tree {
+    type Error = UnknownToken;
+    /// Parses a bracketed tree like `(CP (Spec S) (C' (C Verb) O1))`. An
+    /// unbalanced bracket is reported as the unknown token.
+    fn try_from(s: &str) -> Result<Tree, UnknownToken> {
+        let spaced = s.replace("(", " ( ").replace(")", " ) ");
+        let mut tokens = spaced.split_whitespace().peekable();
+        let tree = Tree::parse(&mut tokens)?;
+        match tokens.next() {
+            Some(t) => Err(UnknownToken(t.to_string())),
+            None => Ok(tree)
+        }
+    }
+}
+
+impl fmt::Display for Tree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_leaf() {
+            return write!(f, "{}", self.label);
+        }
+        write!(f, "({}", self.label)?;
+        for child in self.children.iter() {
+            write!(f, " {}", child)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::convert::TryFrom;
+    use domain::{Colag, Grammar, DomainError};
+    use tree::Tree;
+
+    #[test]
+    fn parses_and_prints_bracketed_trees() {
+        let s = "(CP (Spec S) (C' (C Verb) O1))";
+        let tree = Tree::try_from(s).unwrap();
+        assert_eq!(tree.label, "CP");
+        assert_eq!(tree.children.len(), 2);
+        assert_eq!(tree.children[0], Tree { label: "Spec".to_string(),
+                                            children: vec![Tree::leaf("S")] });
+        assert_eq!(tree.leaves(), vec!["S", "Verb", "O1"]);
+        assert_eq!(tree.depth(), 4);
+        assert_eq!(tree.to_string(), s);
+        assert_eq!(Tree::try_from("  (CP(Spec S )O1)").unwrap().to_string(), "(CP (Spec S) O1)");
+        assert_eq!(Tree::try_from("Verb").unwrap(), Tree::leaf("Verb"));
+    }
+
+    #[test]
+    fn rejects_malformed_trees() {
+        let bad = |s: &str| Tree::try_from(s).err().map(|t| t.0);
+        assert_eq!(bad(""), Some("".to_string()));
+        assert_eq!(bad("(CP (Spec S)"), Some("(".to_string()));
+        assert_eq!(bad("(CP S))"), Some(")".to_string()));
+        assert_eq!(bad(")"), Some(")".to_string()));
+        assert_eq!(bad("(()"), Some("(".to_string()));
+        assert_eq!(bad("()"), Some(")".to_string()));
+        assert_eq!(bad("(CP S) O1"), Some("O1".to_string()));
+    }
+
+    #[test]
+    fn finds_derivations_and_shared_structures() {
+        let ids = "0\t1\t10\n0\t2\t11\n1\t1\t10\n1\t3\t12\n";
+        let trees = "10\t(CP S Verb)\n11\t(CP S Verb O1)\n";
+        let domain = Colag::from_reader(Cursor::new(ids), "ids", 1).unwrap()
+            .read_trees_from(Cursor::new(trees), "trees").unwrap();
+        assert_eq!(domain.derivation(&Grammar(1), &1).unwrap().to_string(), "(CP S Verb)");
+        assert_eq!(domain.same_structure(&Grammar(0), &1).unwrap(),
+                   vec![(Grammar(0), 1), (Grammar(1), 1)]);
+        assert_eq!(domain.same_structure(&Grammar(0), &2).unwrap(), vec![(Grammar(0), 2)]);
+        let shared = domain.shared_structures();
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[&10], vec![(Grammar(0), 1), (Grammar(1), 1)]);
+        match domain.derivation(&Grammar(1), &3) {
+            Err(DomainError::NoDerivation(12)) => (),
+            other => panic!("expected no derivation for tree 12, got {:?}", other)
+        }
+        match domain.derivation(&Grammar(0), &3) {
+            Err(DomainError::NoTree(Grammar(0), 3)) => (),
+            other => panic!("expected no tree, got {:?}", other)
+        }
+    }
+}
@@ -1,53 +1,153 @@
-extern crate csv;
-use std::mem;
-use std::collections::{HashSet, HashMap};
-use std::error::Error;
+use std::io::Read;
+use std::collections::HashMap;
 
-use domain::{TriggerVec, Trigger, Sentence, NUM_PARAMS};
+use domain::{Colag, LanguageDomain, Grammar, Trigger, TriggerVec, Sentence, DomainError, open,
+             data_reader, parse_triggers};
 
+/// The trigger table of the domain: which parameters each sentence is
+/// evidence for.
+pub const NORMAL: &'static str = "normal";
+/// Triggers worked out with extensionally equivalent grammars set aside.
+pub const NO_EQUIV: &'static str = "no-equiv";
+/// Triggers worked out with superset grammars set aside.
+pub const NO_SUPERSET: &'static str = "no-superset";
+
+// The triggers of each sentence in a domain. A domain can hold several of
+// these, by name.
+#[derive(Clone)]
 pub struct TriggerMap(HashMap<Sentence, TriggerVec>);
 
 impl TriggerMap {
+    pub fn new() -> Self {
+        TriggerMap(HashMap::new())
+    }
+
     pub fn sentence(&self, sent: &Sentence) -> Option<&TriggerVec> {
         self.0.get(sent)
     }
 
-    pub fn from_file(filename: &str) -> Result<Self, Box<Error>> {
-        let mut rdr = csv::ReaderBuilder::new()
-            .delimiter(b' ')
-            .has_headers(false)
-            .from_path(filename)
-            .expect(filename);
-
-        let mut triggers = HashMap::new();
-        for result in rdr.deserialize() {
-            let (sentence, trigger_str): (Sentence, String) = result?;
-            assert!(trigger_str.len() == NUM_PARAMS);
-            let mut trigger_vec: Vec<Trigger> = trigger_str
-                .as_bytes()
-                .iter()
-                .map(|b| {
-                    match *b {
-                        b'0' => Trigger::Off,
-                        b'1' => Trigger::On,
-                        b'*' => Trigger::Ambiguous,
-                        b'~' => Trigger::Irrelevant,
-                        _ => panic!("illegal char in irrel str")
-                    }
-                }).collect();
-
-            unsafe {
-                let mut array: TriggerVec = mem::uninitialized();
-                for i in 0..NUM_PARAMS {
-                    array[i] = trigger_vec.remove(0);
-                }
-                triggers.insert(sentence, array);
+    pub fn insert(&mut self, sent: Sentence, triggers: TriggerVec) {
+        self.0.insert(sent, triggers);
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn iter<'a>(&'a self) -> Box<dyn Iterator<Item=(&'a Sentence, &'a TriggerVec)> + 'a> {
+        Box::new(self.0.iter())
+    }
+
+    /// Reads a file of `sentence triggers` lines, with triggers written like
+    /// `~0*110~~0~*~~`, one for each of `num_params` parameters.
+    pub fn from_file(filename: &str, num_params: usize) -> Result<Self, DomainError> {
+        TriggerMap::from_reader(open(filename)?, filename, num_params)
+    }
+
+    /// Reads a trigger table from `source`, which may be compressed. `name`
+    /// identifies it in errors.
+    pub fn from_reader<R: Read>(source: R, name: &str, num_params: usize) -> Result<Self, DomainError> {
+        let mut rdr = data_reader(source, b' ', name)?;
+
+        let mut triggers = TriggerMap::new();
+        for result in rdr.records() {
+            let record = result.map_err(|e| DomainError::csv(name, e))?;
+            let line = record.position().map_or(0, |p| p.line());
+            let (sentence, trigger_str): (Sentence, String) = record.deserialize(None)
+                .map_err(|e| DomainError::parse(name, line, e.to_string()))?;
+            let trigger_vec = parse_triggers(&trigger_str)
+                .map_err(|t| DomainError::bad_token(name, line, t))?;
+            if trigger_vec.len() != num_params {
+                return Err(DomainError::parse(name, line,
+                                              format!("expected {} triggers, saw {}",
+                                                      num_params, trigger_vec.len())));
             }
+            triggers.insert(sentence, trigger_vec);
+        }
+        Ok(triggers)
+    }
+}
 
+// The share of a language's sentences that are each kind of trigger for one
+// parameter.
+#[derive(Clone, Copy, Default)]
+pub struct ParamStats {
+    pub on: f64,
+    pub off: f64,
+    pub ambiguous: f64,
+    pub irrelevant: f64
+}
+
+impl ParamStats {
+    pub fn unambiguous(&self) -> f64 {
+        self.on + self.off
+    }
+}
+
+/// Per-parameter trigger statistics of the language of `target` according to
+/// the trigger table called `table`. Every share of an empty language is 0.
+pub fn param_stats(domain: &Colag, table: &str,
+                   target: &Grammar) -> Result<Vec<ParamStats>, DomainError> {
+    let triggers = domain.trigger_table(table)?;
+    let sentences = domain.language_vec(target)?;
+    let mut stats = vec![ParamStats::default(); domain.num_params()];
+    if sentences.is_empty() {
+        return Ok(stats);
+    }
+    let share = 1. / sentences.len() as f64;
+    for sentence in sentences.iter() {
+        let trigger_vec = triggers.sentence(sentence).ok_or(DomainError::NoTriggers(*sentence))?;
+        for (param, trigger) in trigger_vec.iter().enumerate() {
+            let kind = match *trigger {
+                Trigger::On => &mut stats[param].on,
+                Trigger::Off => &mut stats[param].off,
+                Trigger::Ambiguous => &mut stats[param].ambiguous,
+                Trigger::Irrelevant => &mut stats[param].irrelevant
+            };
+            *kind += share;
+        }
+    }
+    Ok(stats)
+}
+
+/// The languages with no unambiguous trigger for some parameter, and those
+/// parameters. A learner that only moves on triggers never settles them.
+pub fn starved(domain: &Colag, table: &str) -> Result<Vec<(Grammar, Vec<usize>)>, DomainError> {
+    let mut starved = Vec::new();
+    for grammar in domain.grammars().iter() {
+        let params: Vec<usize> = param_stats(domain, table, grammar)?.iter()
+            .enumerate()
+            .filter(|&(_, stats)| stats.unambiguous() == 0.)
+            .map(|(param, _)| param)
+            .collect();
+        if !params.is_empty() {
+            starved.push((*grammar, params));
         }
-        assert!(triggers.len() == 48077,
-                format!("expected 48077 sentences, saw {}",
-                triggers.len()));
-        Ok(TriggerMap(triggers))
+    }
+    Ok(starved)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use domain::{Colag, Grammar, Sentence};
+    use triggers;
+    use triggers::NORMAL;
+
+    #[test]
+    fn stats_of_an_empty_language_are_zero() {
+        let mut language: HashMap<Grammar, HashSet<Sentence>> = HashMap::new();
+        language.insert(Grammar(0), HashSet::new());
+        language.insert(Grammar(1), vec![1, 2].into_iter().collect());
+        let mut domain = Colag::from_languages(1, language);
+        domain.gen_triggers();
+        let stats = triggers::param_stats(&domain, NORMAL, &Grammar(0)).unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].on, stats[0].off, stats[0].ambiguous, stats[0].irrelevant),
+                   (0., 0., 0., 0.));
+        let stats = triggers::param_stats(&domain, NORMAL, &Grammar(1)).unwrap();
+        assert_eq!(stats[0].on, 1.);
+        // with nothing to say, the empty language has no triggers
+        assert_eq!(triggers::starved(&domain, NORMAL).unwrap(), vec![(Grammar(0), vec![0])]);
     }
 }
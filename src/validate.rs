@@ -0,0 +1,357 @@
+use std::fmt;
+use std::convert::TryFrom;
+use std::collections::{HashMap, HashSet};
+
+use domain::{Colag, LanguageDomain, Grammar, Sentence, Trigger, TriggerVec, DomainError,
+             open_data, parse_triggers, COLAG_PARAMS};
+use sentence::{SurfaceForm, Illoc};
+
+// How many problems of each kind to print before summarising the rest.
+const EXAMPLES: usize = 10;
+
+// The outcome of one integrity check: a description of what was checked and
+// everything that failed it.
+pub struct Check {
+    pub name: String,
+    pub problems: Vec<String>
+}
+
+impl Check {
+    fn new(name: &str) -> Self {
+        Check { name: name.to_string(), problems: Vec::new() }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.problems.is_empty() {
+            return write!(f, "ok: {}", self.name);
+        }
+        write!(f, "FAILED: {} ({} problems)", self.name, self.problems.len())?;
+        for problem in self.problems.iter().take(EXAMPLES) {
+            write!(f, "\n    {}", problem)?;
+        }
+        if self.problems.len() > EXAMPLES {
+            write!(f, "\n    ...")?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Report {
+    pub checks: Vec<Check>
+}
+
+impl Report {
+    pub fn ok(&self) -> bool {
+        self.checks.iter().all(|c| c.problems.is_empty())
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for check in self.checks.iter() {
+            writeln!(f, "{}", check)?;
+        }
+        Ok(())
+    }
+}
+
+// The data files that make up a COLAG style domain.
+pub struct DataFiles<'a> {
+    pub ids: &'a str,
+    pub sentences: &'a str,
+    pub triggers: Vec<&'a str>
+}
+
+impl<'a> DataFiles<'a> {
+    pub fn colag() -> Self {
+        DataFiles {
+            ids: "./data/COLAG_2011_ids.txt",
+            sentences: "./data/COLAG_2011_sents.txt",
+            triggers: vec!["./data/irrelevance-output.txt",
+                           "./data/irrelevance-output-no-equiv.txt",
+                           "./data/irrelevance-output-no-superset.txt"]
+        }
+    }
+}
+
+/// Loads every file in `files` and cross-checks them against each other and
+/// against the published COLAG counts, carrying on past problems so that the
+/// report covers everything that's wrong.
+pub fn validate(files: &DataFiles) -> Report {
+    let mut checks = Vec::new();
+
+    let mut loading = Check::new(&format!("read {}", files.ids));
+    let domain = match Colag::from_file(files.ids, COLAG_PARAMS) {
+        Ok(domain) => domain,
+        Err(e) => {
+            // nothing else can be checked without the languages
+            loading.problems.push(e.to_string());
+            checks.push(loading);
+            return Report { checks };
+        }
+    };
+    checks.push(loading);
+    checks.push(counts(&domain));
+
+    let mut domain = domain;
+    let mut loading = Check::new(&format!("read {}", files.sentences));
+    let duplicates = read_surface_forms(&mut domain, files.sentences, &mut loading);
+    checks.push(loading);
+    checks.push(surface_forms(&domain, files.sentences, &duplicates));
+    checks.push(unique_surface_forms(&domain, files.sentences));
+
+    let generators = domain.sentence_generators();
+    for filename in files.triggers.iter() {
+        let mut loading = Check::new(&format!("read {}", filename));
+        let table = read_trigger_table(filename, domain.num_params(), &mut loading);
+        checks.push(loading);
+        checks.push(trigger_coverage(&domain, filename, &table));
+        checks.push(trigger_agreement(&domain, filename, &table, generators));
+    }
+    Report { checks }
+}
+
+fn counts(domain: &Colag) -> Check {
+    let mut check = Check::new("COLAG counts");
+    if domain.grammars().len() != 3072 {
+        check.problems.push(format!("expected 3072 languages, saw {}", domain.grammars().len()));
+    }
+    if domain.all_sentences().len() != 48077 {
+        check.problems.push(format!("expected 48077 sentences, saw {}",
+                                    domain.all_sentences().len()));
+    }
+    match domain.language(&Grammar(611)) {
+        Ok(english) if english.len() != 360 =>
+            check.problems.push(format!("expected 360 sentences in English (611), saw {}",
+                                        english.len())),
+        Err(e) => check.problems.push(format!("no English: {}", e)),
+        _ => ()
+    }
+    check
+}
+
+// Reads a sentences file line by line into `domain`, noting bad lines in
+// `check` instead of giving up on them. Returns the line of each surface form
+// given for a sentence that already had one; the later form wins.
+fn read_surface_forms(domain: &mut Colag, filename: &str,
+                      check: &mut Check) -> Vec<(Sentence, u64)> {
+    let mut duplicates = Vec::new();
+    let mut rdr = match open_data(filename, b'\t') {
+        Ok(rdr) => rdr,
+        Err(e) => {
+            check.problems.push(e.to_string());
+            return duplicates;
+        }
+    };
+    let mut seen = HashSet::new();
+    for result in rdr.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                check.problems.push(DomainError::csv(filename, e).to_string());
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line());
+        let (sentence, illoc, form): (Sentence, String, String) = match record.deserialize(None) {
+            Ok(fields) => fields,
+            Err(e) => {
+                check.problems.push(DomainError::parse(filename, line, e.to_string()).to_string());
+                continue;
+            }
+        };
+        let parsed = Illoc::try_from(illoc.trim())
+            .and_then(|illoc| SurfaceForm::try_from(form.trim()).map(|form| (illoc, form)));
+        match parsed {
+            Err(t) => check.problems.push(DomainError::bad_token(filename, line, t).to_string()),
+            Ok((illoc, mut form)) => {
+                form.illoc = illoc;
+                if !seen.insert(sentence) {
+                    duplicates.push((sentence, line));
+                }
+                domain.add_surface_form(sentence, form);
+            }
+        }
+    }
+    sort_by_position(&mut check.problems);
+    duplicates
+}
+
+fn surface_forms(domain: &Colag, filename: &str, duplicates: &[(Sentence, u64)]) -> Check {
+    let mut check = Check::new(&format!("every sentence has exactly one surface form in {}",
+                                        filename));
+    for sentence in domain.all_sentences().iter() {
+        if domain.surface_form(sentence).is_err() {
+            check.problems.push(format!("no surface form for sentence {}", sentence));
+        }
+    }
+    for &(sentence, line) in duplicates.iter() {
+        check.problems.push(format!("{}:{}: second surface form for sentence {}",
+                                    filename, line, sentence));
+    }
+    let known: HashSet<&Sentence> = domain.all_sentences().iter().collect();
+    let mut orphans: Vec<&Sentence> = domain.surface_forms()
+        .filter(|s| !known.contains(s))
+        .collect();
+    orphans.sort();
+    for sentence in orphans {
+        check.problems.push(format!("surface form for sentence {}, which no grammar generates",
+                                    sentence));
+    }
+    check
+}
+
+// Looking sentences up by surface form needs every form to be different.
+fn unique_surface_forms(domain: &Colag, filename: &str) -> Check {
+    let mut check = Check::new(&format!("no two sentences share a surface form in {}", filename));
+    let mut seen: HashMap<String, Sentence> = HashMap::new();
+    for sentence in domain.all_sentences().iter() {
+        if let Ok(form) = domain.surface_form(sentence) {
+            if let Some(other) = seen.insert(form.to_string(), *sentence) {
+                check.problems.push(format!("sentences {} and {} are both `{}`",
+                                            other, sentence, form));
+            }
+        }
+    }
+    check
+}
+
+// Reads a trigger table line by line, noting bad lines in `check` instead of
+// giving up on them.
+fn read_trigger_table(filename: &str, num_params: usize,
+                      check: &mut Check) -> HashMap<Sentence, TriggerVec> {
+    let mut table = HashMap::new();
+    let mut rdr = match open_data(filename, b' ') {
+        Ok(rdr) => rdr,
+        Err(e) => {
+            check.problems.push(e.to_string());
+            return table;
+        }
+    };
+    for result in rdr.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                check.problems.push(DomainError::csv(filename, e).to_string());
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line());
+        let (sentence, trigger_str): (Sentence, String) = match record.deserialize(None) {
+            Ok(fields) => fields,
+            Err(e) => {
+                check.problems.push(DomainError::parse(filename, line, e.to_string()).to_string());
+                continue;
+            }
+        };
+        match parse_triggers(&trigger_str) {
+            Err(t) => check.problems.push(DomainError::bad_token(filename, line, t).to_string()),
+            Ok(ref triggers) if triggers.len() != num_params =>
+                check.problems.push(format!("{}:{}: expected {} triggers, saw {}", filename,
+                                            line, num_params, triggers.len())),
+            Ok(triggers) => {
+                if table.insert(sentence, triggers).is_some() {
+                    check.problems.push(format!("{}:{}: second trigger vector for sentence {}",
+                                                filename, line, sentence));
+                }
+            }
+        }
+    }
+    sort_by_position(&mut check.problems);
+    table
+}
+
+// Sorts problems that start `file:line:` by file and then line number, and
+// puts the others, which can't be placed, first.
+fn sort_by_position(problems: &mut Vec<String>) {
+    problems.sort_by_key(|problem| {
+        let fields: Vec<&str> = problem.splitn(3, ':').collect();
+        match fields.get(1).and_then(|line| line.parse::<u64>().ok()) {
+            Some(line) if fields.len() == 3 => Some((fields[0].to_string(), line)),
+            _ => None
+        }
+    });
+}
+
+fn trigger_coverage(domain: &Colag, filename: &str, table: &HashMap<Sentence, TriggerVec>) -> Check {
+    let mut check = Check::new(&format!("every sentence has triggers in {}", filename));
+    for sentence in domain.all_sentences().iter() {
+        if !table.contains_key(sentence) {
+            check.problems.push(format!("no triggers for sentence {}", sentence));
+        }
+    }
+    let known: HashSet<&Sentence> = domain.all_sentences().iter().collect();
+    let mut orphans: Vec<&Sentence> = table.keys().filter(|s| !known.contains(s)).collect();
+    orphans.sort();
+    for sentence in orphans {
+        check.problems.push(format!("triggers for sentence {}, which no grammar generates",
+                                    sentence));
+    }
+    check
+}
+
+// An On (Off) trigger for a parameter means every grammar that generates the
+// sentence has that parameter on (off).
+fn trigger_agreement(domain: &Colag, filename: &str, table: &HashMap<Sentence, TriggerVec>,
+                     generators: &HashMap<Sentence, Vec<Grammar>>) -> Check {
+    let mut check = Check::new(&format!("On/Off triggers in {} agree with the grammars", filename));
+    let num_params = domain.num_params();
+    let mut sentences: Vec<&Sentence> = table.keys().collect();
+    sentences.sort();
+    for sentence in sentences {
+        let grammars = match generators.get(sentence) {
+            Some(grammars) => grammars,
+            None => continue  // an orphan, reported by trigger_coverage
+        };
+        for (param, trigger) in table[sentence].iter().enumerate() {
+            let expected = match *trigger {
+                Trigger::On => true,
+                Trigger::Off => false,
+                _ => continue
+            };
+            if let Some(g) = grammars.iter().find(|g| g.get(param, num_params) != expected) {
+                check.problems.push(format!("sentence {} triggers parameter {} {:?}, but grammar {} generates it",
+                                            sentence, param, trigger, g));
+            }
+        }
+    }
+    check
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use validate::{validate, DataFiles};
+
+    #[test]
+    fn duplicate_sentences_and_problems_are_reported_in_line_order() {
+        let dir = env::temp_dir();
+        let ids = dir.join(format!("validate-ids-{}.txt", ::std::process::id()));
+        let sents = dir.join(format!("validate-sents-{}.txt", ::std::process::id()));
+        let lines: String = (0..12).map(|s| format!("0\t{}\t{}\n", s, s)).collect();
+        fs::write(&ids, lines).unwrap();
+        // lines 9 and 10 don't parse, and line 12 repeats sentence 0
+        let mut lines: Vec<String> = (0..8).map(|s| format!("{}\tDEC\tS Verb", s)).collect();
+        lines.push("8\tDEC\tS Florp".to_string());
+        lines.push("9\tDEC\tS Verb Blip".to_string());
+        lines.push("10\tDEC\tS Aux Verb".to_string());
+        lines.push("0\tDEC\tS Verb O1".to_string());
+        fs::write(&sents, lines.join("\n") + "\n").unwrap();
+        let files = DataFiles { ids: ids.to_str().unwrap(), sentences: sents.to_str().unwrap(),
+                                triggers: vec![] };
+        let report = validate(&files);
+        fs::remove_file(&ids).unwrap();
+        fs::remove_file(&sents).unwrap();
+
+        let reading = &report.checks[2].problems;
+        assert_eq!(reading.len(), 2, "{:?}", reading);
+        assert!(reading[0].contains(":9:") && reading[1].contains(":10:"), "{:?}", reading);
+        let forms = &report.checks[3].problems;
+        assert!(forms.iter().any(|p| p.ends_with(":12: second surface form for sentence 0")),
+                "{:?}", forms);
+        assert!(forms.iter().any(|p| p == "no surface form for sentence 11"), "{:?}", forms);
+    }
+}